@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raft::{CachedCommand, Command, CommandDecodeError, CustomCommand, ServerId};
+use serde_json::Value as JsonValue;
+
+struct NoCustom;
+
+impl CustomCommand for NoCustom {
+    fn command_type(&self) -> &'static str {
+        "NoCustom"
+    }
+
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Null
+    }
+
+    fn from_json(_json: &JsonValue) -> Result<Self, CommandDecodeError> {
+        Ok(NoCustom)
+    }
+}
+
+fn configuration() -> Command<NoCustom> {
+    Command::JointConfiguration{
+        old_configuration: (0..100).rev().map(ServerId).collect(),
+        new_configuration: (50..150).rev().map(ServerId).collect(),
+        change_id: None,
+    }
+}
+
+fn encode_configuration(c: &mut Criterion) {
+    let command = configuration();
+    c.bench_function("Command::to_json", |b| {
+        b.iter(|| black_box(&command).to_json())
+    });
+
+    let cached = CachedCommand::new(configuration());
+    c.bench_function("CachedCommand::to_json", |b| {
+        b.iter(|| black_box(&cached).to_json())
+    });
+}
+
+criterion_group!(benches, encode_configuration);
+criterion_main!(benches);
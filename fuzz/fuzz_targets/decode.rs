@@ -0,0 +1,43 @@
+#![no_main]
+
+// Every decoding entry point must return, never panic, whatever JSON it is
+// handed. Run with `cargo fuzz run decode`.
+
+use libfuzzer_sys::fuzz_target;
+use raft::{Command, CommandDecodeError, CustomCommand, DecodeOptions, LogEntry, LogEntryFactory};
+use serde_json::Value as JsonValue;
+use std::convert::TryFrom;
+
+struct Opaque(JsonValue);
+
+impl CustomCommand for Opaque {
+    fn command_type(&self) -> &'static str {
+        "Opaque"
+    }
+
+    fn to_json(&self) -> JsonValue {
+        self.0.clone()
+    }
+
+    fn from_json(json: &JsonValue) -> Result<Self, CommandDecodeError> {
+        Ok(Opaque(json.clone()))
+    }
+}
+
+fuzz_target!(|bytes: &[u8]| {
+    let strict = DecodeOptions {
+        coerce_string_term: true,
+        strict_configuration: true,
+        max_depth: Some(32),
+        require_term: true,
+        ..DecodeOptions::default()
+    };
+    let _ = LogEntry::<Opaque>::decode_slice(bytes, &strict);
+    if let Ok(json) = serde_json::from_slice::<JsonValue>(bytes) {
+        let _ = LogEntry::<Opaque>::try_from(&json);
+        let _ = LogEntry::<Opaque>::try_from(json.clone());
+        let _ = Command::<Opaque>::try_from(&json);
+        let _ = LogEntry::<Opaque>::decode(&json, &DecodeOptions::default());
+        let _ = LogEntryFactory::<Opaque>::new().decode(&json);
+    }
+});
@@ -0,0 +1,553 @@
+use crate::{
+    decode::{DecodeOptions, LogEntryError},
+    log_entry::{sorted_instance_ids, Command, CustomCommand, LogEntry},
+    server_id::ServerId,
+};
+use serde::{
+    ser::SerializeMap,
+    Serialize,
+    Serializer,
+};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct AppendEntriesRequest<T> {
+    pub term: u64,
+    pub leader_id: ServerId,
+    pub prev_log_index: usize,
+    pub prev_log_term: u64,
+    pub entries: Vec<LogEntry<T>>,
+    pub leader_commit: usize,
+}
+
+// The wire formats a node can speak, so that during a rolling upgrade a
+// newer node can still talk to an older one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WireVersion {
+    // Configuration entries carry neither change ids nor learners, only
+    // configuration and custom commands exist, and rejections carry no
+    // conflict hint.
+    V1,
+    V2,
+}
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum WireVersionError {
+    #[error("entry {position} is a {command_type} command, which wire version 1 cannot represent")]
+    UnsupportedCommand{
+        position: usize,
+        command_type: String,
+    },
+    #[error("entry {position} has learners, which wire version 1 cannot represent")]
+    Learners{
+        position: usize,
+    },
+}
+
+// Fails on the first entry a `WireVersion::V1` peer would misread. Change
+// ids are only a hint and may be dropped, but learners, and the commands V1
+// predates, would silently change the entry's meaning.
+fn check_v1_representable<T: CustomCommand>(entries: &[LogEntry<T>]) -> Result<(), WireVersionError> {
+    for (position, entry) in entries.iter().enumerate() {
+        match &entry.command {
+            Some(command @ (Command::Batch(_) | Command::NoOp | Command::ClientData(_))) => {
+                return Err(WireVersionError::UnsupportedCommand{
+                    position,
+                    command_type: String::from(command.command_type()),
+                });
+            },
+            Some(Command::SingleConfiguration{learners, ..}) if !learners.is_empty() => {
+                return Err(WireVersionError::Learners{position});
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+// Removes the fields a `WireVersion::V1` peer does not know about from an
+// encoded entry.
+fn downgrade_to_v1(entry: &mut JsonValue) {
+    let is_configuration = matches!(
+        entry.get("type").and_then(JsonValue::as_str),
+        Some("SingleConfiguration") | Some("JointConfiguration")
+    );
+    if let (true, Some(JsonValue::Object(command))) = (is_configuration, entry.get_mut("command")) {
+        command.remove("changeId");
+        command.remove("learners");
+    }
+}
+
+fn decode_field(json: &JsonValue, field: &'static str) -> Result<u64, LogEntryError> {
+    json.get(field)
+        .and_then(JsonValue::as_u64)
+        .ok_or(LogEntryError::MissingField(field))
+}
+
+impl<T: CustomCommand> AppendEntriesRequest<T> {
+    pub fn to_json_versioned(&self, version: WireVersion) -> Result<JsonValue, WireVersionError> {
+        let mut json = self.to_json();
+        if version == WireVersion::V1 {
+            check_v1_representable(&self.entries)?;
+            if let Some(entries) = json["entries"].as_array_mut() {
+                entries.iter_mut().for_each(downgrade_to_v1);
+            }
+        }
+        Ok(json)
+    }
+
+    // Decodes a request sent by a peer speaking `version`. Fields that
+    // `version` does not define are ignored even if present.
+    pub fn from_json_versioned(json: &JsonValue, version: WireVersion) -> Result<Self, LogEntryError> {
+        let entries = json
+            .get("entries")
+            .and_then(JsonValue::as_array)
+            .ok_or(LogEntryError::MissingField("entries"))?
+            .iter()
+            .map(|entry| match version {
+                WireVersion::V1 => {
+                    let mut entry = entry.clone();
+                    downgrade_to_v1(&mut entry);
+                    LogEntry::decode(&entry, &DecodeOptions::default())
+                },
+                WireVersion::V2 => LogEntry::decode(entry, &DecodeOptions::default()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            term: decode_field(json, "term")?,
+            leader_id: ServerId(decode_field(json, "leaderId")?),
+            prev_log_index: decode_field(json, "prevLogIndex")? as usize,
+            prev_log_term: decode_field(json, "prevLogTerm")?,
+            entries,
+            leader_commit: decode_field(json, "leaderCommit")? as usize,
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "term": self.term,
+            "leaderId": self.leader_id,
+            "prevLogIndex": self.prev_log_index,
+            "prevLogTerm": self.prev_log_term,
+            "entries": self.entries.iter().map(LogEntry::to_json).collect::<Vec<_>>(),
+            "leaderCommit": self.leader_commit,
+        })
+    }
+
+    // Produces the same document as `to_json`, but writes entries straight
+    // into the output instead of building a `JsonValue` for each of them.
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(&RequestSerializer(self))
+            .expect("serializing to a string cannot fail")
+    }
+}
+
+struct RequestSerializer<'a, T>(&'a AppendEntriesRequest<T>);
+
+impl<T: CustomCommand> Serialize for RequestSerializer<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let request = self.0;
+        let mut map = serializer.serialize_map(Some(6))?;
+        map.serialize_entry("term", &request.term)?;
+        map.serialize_entry("leaderId", &request.leader_id)?;
+        map.serialize_entry("prevLogIndex", &request.prev_log_index)?;
+        map.serialize_entry("prevLogTerm", &request.prev_log_term)?;
+        map.serialize_entry("entries", &EntriesSerializer(&request.entries))?;
+        map.serialize_entry("leaderCommit", &request.leader_commit)?;
+        map.end()
+    }
+}
+
+struct EntriesSerializer<'a, T>(&'a [LogEntry<T>]);
+
+impl<T: CustomCommand> Serialize for EntriesSerializer<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(EntrySerializer))
+    }
+}
+
+struct EntrySerializer<'a, T>(&'a LogEntry<T>);
+
+impl<T: CustomCommand> Serialize for EntrySerializer<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entry = self.0;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("term", &entry.term)?;
+        if let Some(command) = &entry.command {
+            map.serialize_entry("type", command.command_type())?;
+            match command {
+                Command::SingleConfiguration{old_configuration, configuration, learners, change_id} => map.serialize_entry(
+                    "command",
+                    &ConfigurationSerializer{
+                        configurations: [
+                            ("configuration", configuration),
+                            ("oldConfiguration", old_configuration),
+                        ],
+                        learners: Some(learners).filter(|learners| !learners.is_empty()),
+                        change_id: *change_id,
+                    },
+                )?,
+                Command::JointConfiguration{old_configuration, new_configuration, change_id} => map.serialize_entry(
+                    "command",
+                    &ConfigurationSerializer{
+                        configurations: [
+                            ("newConfiguration", new_configuration),
+                            ("oldConfiguration", old_configuration),
+                        ],
+                        learners: None,
+                        change_id: *change_id,
+                    },
+                )?,
+                Command::Batch(_) | Command::NoOp | Command::ClientData(_) => map.serialize_entry("command", &command.to_json())?,
+                Command::Custom(custom_command) => map.serialize_entry("command", &custom_command.to_json())?,
+            }
+        }
+        map.end()
+    }
+}
+
+struct ConfigurationSerializer<'a> {
+    configurations: [(&'static str, &'a HashSet<ServerId>); 2],
+    learners: Option<&'a HashSet<ServerId>>,
+    change_id: Option<u64>,
+}
+
+impl Serialize for ConfigurationSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(change_id) = self.change_id {
+            map.serialize_entry("changeId", &change_id)?;
+        }
+        for (field, instance_ids) in &self.configurations {
+            map.serialize_entry(field, &InstanceIdsSerializer(instance_ids))?;
+        }
+        if let Some(learners) = self.learners {
+            map.serialize_entry("learners", &InstanceIdsSerializer(learners))?;
+        }
+        map.end()
+    }
+}
+
+struct InstanceIdsSerializer<'a>(&'a HashSet<ServerId>);
+
+impl Serialize for InstanceIdsSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("instanceIds", &sorted_instance_ids(self.0))?;
+        map.end()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct AppendEntriesResponse {
+    pub term: u64,
+    pub success: bool,
+    // Echoed back from the request so a pipelining leader can tell which
+    // request this response belongs to.
+    pub prev_log_index: usize,
+    pub entries_len: usize,
+    // On rejection, where the follower's log diverges, as reported by
+    // `Log::append_entries`. Not sent to `WireVersion::V1` peers.
+    pub conflict_index: Option<usize>,
+    pub conflict_term: Option<u64>,
+}
+
+impl AppendEntriesResponse {
+    pub fn for_request<T>(
+        request: &AppendEntriesRequest<T>,
+        term: u64,
+        success: bool,
+    ) -> Self {
+        Self {
+            term,
+            success,
+            prev_log_index: request.prev_log_index,
+            entries_len: request.entries.len(),
+            conflict_index: None,
+            conflict_term: None,
+        }
+    }
+
+    pub fn matches_request<T>(&self, request: &AppendEntriesRequest<T>) -> bool {
+        self.prev_log_index == request.prev_log_index
+            && self.entries_len == request.entries.len()
+    }
+
+    pub fn match_index(&self) -> usize {
+        self.prev_log_index + self.entries_len
+    }
+
+    pub fn to_json_versioned(&self, version: WireVersion) -> JsonValue {
+        let mut json = json!({
+            "term": self.term,
+            "success": self.success,
+            "prevLogIndex": self.prev_log_index,
+            "entriesLen": self.entries_len,
+        });
+        if version == WireVersion::V2 {
+            if let Some(conflict_index) = self.conflict_index {
+                json["conflictIndex"] = json!(conflict_index);
+            }
+            if let Some(conflict_term) = self.conflict_term {
+                json["conflictTerm"] = json!(conflict_term);
+            }
+        }
+        json
+    }
+
+    // Decodes a response sent by a peer speaking `version`. A V1 response
+    // never carries a conflict hint, even if the fields are present.
+    pub fn from_json_versioned(json: &JsonValue, version: WireVersion) -> Result<Self, LogEntryError> {
+        let (conflict_index, conflict_term) = match version {
+            WireVersion::V1 => (None, None),
+            WireVersion::V2 => (
+                json.get("conflictIndex").and_then(JsonValue::as_u64).map(|index| index as usize),
+                json.get("conflictTerm").and_then(JsonValue::as_u64),
+            ),
+        };
+        Ok(Self {
+            term: decode_field(json, "term")?,
+            success: json
+                .get("success")
+                .and_then(JsonValue::as_bool)
+                .ok_or(LogEntryError::MissingField("success"))?,
+            prev_log_index: decode_field(json, "prevLogIndex")? as usize,
+            entries_len: decode_field(json, "entriesLen")? as usize,
+            conflict_index,
+            conflict_term,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{servers, Payload};
+
+    fn request(prev_log_index: usize, num_entries: usize) -> AppendEntriesRequest<Payload> {
+        AppendEntriesRequest {
+            term: 3,
+            leader_id: ServerId(1),
+            prev_log_index,
+            prev_log_term: 2,
+            entries: (0..num_entries)
+                .map(|_| LogEntry {
+                    term: 3,
+                    command: None,
+                })
+                .collect(),
+            leader_commit: 0,
+        }
+    }
+
+    #[test]
+    fn response_matches_request_it_answers() {
+        //Arrange
+        let request = request(5, 3);
+
+        //Act
+        let response = AppendEntriesResponse::for_request(&request, 3, true);
+
+        //Assert
+        assert!(response.matches_request(&request));
+        assert_eq!(8, response.match_index());
+    }
+
+    #[test]
+    fn stale_response_does_not_match_newer_request() {
+        //Arrange
+        let stale_request = request(5, 3);
+        let stale_response = AppendEntriesResponse::for_request(&stale_request, 3, true);
+        let newer_request = request(8, 2);
+
+        //Act
+        let matches = stale_response.matches_request(&newer_request);
+
+        //Assert
+        assert!(!matches);
+        assert!(!stale_response.matches_request(&request(5, 2)));
+    }
+
+    #[test]
+    fn streamed_string_matches_json_value() {
+        //Arrange
+        let request = AppendEntriesRequest {
+            term: 4,
+            leader_id: ServerId(2),
+            prev_log_index: 10,
+            prev_log_term: 3,
+            entries: vec![
+                LogEntry {
+                    term: 3,
+                    command: None,
+                },
+                LogEntry {
+                    term: 4,
+                    command: Some(Command::SingleConfiguration{
+                        old_configuration: servers!(3, 1, 2),
+                        configuration: servers!(4, 2, 3, 1),
+                        learners: servers!(),
+                        change_id: None,
+                    }),
+                },
+                LogEntry {
+                    term: 4,
+                    command: Some(Command::JointConfiguration{
+                        old_configuration: servers!(1, 2, 3),
+                        new_configuration: servers!(5, 4, 3),
+                        change_id: None,
+                    }),
+                },
+                LogEntry {
+                    term: 4,
+                    command: Some(Command::Custom(Payload(42))),
+                },
+            ],
+            leader_commit: 9,
+        };
+
+        //Act
+        let streamed = request.to_json_string();
+
+        //Assert
+        assert_eq!(
+            request.to_json(),
+            serde_json::from_str::<JsonValue>(&streamed).unwrap()
+        );
+    }
+
+    fn configuration_request() -> AppendEntriesRequest<Payload> {
+        AppendEntriesRequest {
+            term: 4,
+            leader_id: ServerId(2),
+            prev_log_index: 10,
+            prev_log_term: 3,
+            entries: vec![
+                LogEntry {
+                    term: 4,
+                    command: Some(Command::SingleConfiguration{
+                        old_configuration: servers!(1, 2, 3),
+                        configuration: servers!(1, 2, 3),
+                        learners: servers!(4),
+                        change_id: Some(7),
+                    }),
+                },
+                LogEntry {
+                    term: 4,
+                    command: Some(Command::Custom(Payload(42))),
+                },
+            ],
+            leader_commit: 9,
+        }
+    }
+
+    #[test]
+    fn v2_round_trips_change_ids_and_learners() {
+        //Arrange
+        let request = configuration_request();
+
+        //Act
+        let encoded = request.to_json_versioned(WireVersion::V2).unwrap();
+        let decoded = AppendEntriesRequest::<Payload>::from_json_versioned(&encoded, WireVersion::V2).unwrap();
+
+        //Assert
+        assert_eq!(request.to_json(), encoded);
+        assert_eq!(request, decoded);
+        assert_eq!(Some(7), decoded.entries[0].command.as_ref().and_then(Command::change_id));
+    }
+
+    #[test]
+    fn v1_omits_change_ids() {
+        //Arrange
+        let mut request = configuration_request();
+        request.entries[0].command = Some(Command::SingleConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            configuration: servers!(1, 2, 3, 4),
+            learners: servers!(),
+            change_id: Some(7),
+        });
+
+        //Act
+        let encoded = request.to_json_versioned(WireVersion::V1).unwrap();
+        let decoded = AppendEntriesRequest::<Payload>::from_json_versioned(&encoded, WireVersion::V1).unwrap();
+        let from_v2 = AppendEntriesRequest::<Payload>::from_json_versioned(
+            &configuration_request().to_json_versioned(WireVersion::V2).unwrap(),
+            WireVersion::V1
+        ).unwrap();
+
+        //Assert
+        assert_eq!(None, encoded["entries"][0]["command"].get("changeId"));
+        assert_eq!(
+            Some(&LogEntry {
+                term: 4,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(1, 2, 3),
+                    configuration: servers!(1, 2, 3, 4),
+                    learners: servers!(),
+                    change_id: None,
+                }),
+            }),
+            decoded.entries.first()
+        );
+        assert!(matches!(
+            &from_v2.entries[0].command,
+            Some(Command::SingleConfiguration{learners, change_id: None, ..}) if learners.is_empty()
+        ));
+        assert_eq!(request.entries[1], decoded.entries[1]);
+    }
+
+    #[test]
+    fn v1_refuses_entries_it_cannot_represent() {
+        //Arrange
+        let with_learners = configuration_request();
+        let mut with_no_op = configuration_request();
+        with_no_op.entries[0].command = Some(Command::NoOp);
+        let mut with_client_data = configuration_request();
+        with_client_data.entries[0].command = Some(Command::Custom(Payload(1)));
+        with_client_data.entries[1].command = Some(Command::ClientData(vec![1, 2]));
+
+        //Act
+        let learners = with_learners.to_json_versioned(WireVersion::V1);
+        let no_op = with_no_op.to_json_versioned(WireVersion::V1);
+        let client_data = with_client_data.to_json_versioned(WireVersion::V1);
+
+        //Assert
+        assert_eq!(Err(WireVersionError::Learners{position: 0}), learners);
+        assert_eq!(
+            Err(WireVersionError::UnsupportedCommand{position: 0, command_type: String::from("NoOp")}),
+            no_op
+        );
+        assert_eq!(
+            Err(WireVersionError::UnsupportedCommand{position: 1, command_type: String::from("ClientData")}),
+            client_data
+        );
+    }
+
+    #[test]
+    fn conflict_hint_is_only_sent_in_v2() {
+        //Arrange
+        let response = AppendEntriesResponse {
+            conflict_index: Some(3),
+            conflict_term: Some(2),
+            ..AppendEntriesResponse::for_request(&request(5, 3), 4, false)
+        };
+
+        //Act
+        let v1 = response.to_json_versioned(WireVersion::V1);
+        let v2 = response.to_json_versioned(WireVersion::V2);
+
+        //Assert
+        assert_eq!(None, v1.get("conflictTerm"));
+        assert_eq!(None, v1.get("conflictIndex"));
+        assert_eq!(Ok(response), AppendEntriesResponse::from_json_versioned(&v2, WireVersion::V2));
+        assert_eq!(
+            Ok(AppendEntriesResponse::for_request(&request(5, 3), 4, false)),
+            AppendEntriesResponse::from_json_versioned(&v2, WireVersion::V1)
+        );
+    }
+}
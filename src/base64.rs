@@ -0,0 +1,74 @@
+// Standard, padded base64 (RFC 4648 §4), used to carry raw bytes in JSON.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (position, &byte)| group | (byte as u32) << (16 - 8 * position));
+        for position in 0..4 {
+            if position <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * position) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Returns `None` unless `encoded` is well-formed, padding included.
+pub(crate) fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(encoded.len() / 4 * 3);
+    for (number, chunk) in encoded.chunks(4).enumerate() {
+        let is_last = number == encoded.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&symbol| symbol == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+        let mut group = 0u32;
+        for &symbol in &chunk[..4 - padding] {
+            let value = ALPHABET.iter().position(|&letter| letter == symbol)?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding;
+        bytes.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_rfc_4648_vectors() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encoded, encode(bytes.as_bytes()));
+            assert_eq!(Some(bytes.as_bytes().to_vec()), decode(encoded));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(None, decode("Zg="));
+        assert_eq!(None, decode("Zg==Zg=="));
+        assert_eq!(None, decode("Z==="));
+        assert_eq!(None, decode("Zm9*"));
+    }
+}
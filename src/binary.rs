@@ -0,0 +1,385 @@
+// A compact binary form of an entry for on-disk storage. The layout below is
+// version 1 of the format, the version `stable_bytes` leads with; any change
+// to it bumps `STABLE_FORMAT_VERSION`.
+//
+//   entry         = term:u64 tag:u8 body
+//   tag 0         no command; empty body
+//   tag 1         SingleConfiguration: ids(old) ids(new) ids(learners) change_id
+//   tag 2         JointConfiguration: ids(old) ids(new) change_id
+//   tag 3         Custom: length:u32 payload, from `CustomCommand::to_bytes`
+//   tag 4         Batch: count:u32, then count times tag:u8 body
+//   tag 5         NoOp; empty body
+//   tag 6         ClientData: length:u32 data
+//   ids           count:u32, then count ids as u64, ascending
+//   change_id     flag:u8, 0 for none or 1 followed by the id as u64
+//
+// Integers are big-endian, as in the frame format. Sorting the id sets
+// means equal entries always encode identically. Any other tag or flag
+// value, and bytes left after the entry, are rejected.
+
+use crate::{
+    decode::LogEntryError,
+    log_entry::{sorted_instance_ids, Command, CustomCommand, LogEntry},
+    server_id::ServerId,
+};
+use std::collections::HashSet;
+
+const NO_COMMAND: u8 = 0;
+const SINGLE_CONFIGURATION: u8 = 1;
+const JOINT_CONFIGURATION: u8 = 2;
+const CUSTOM: u8 = 3;
+const BATCH: u8 = 4;
+const NO_OP: u8 = 5;
+const CLIENT_DATA: u8 = 6;
+
+// Leads every `stable_bytes` encoding. Bumped only in a major release.
+const STABLE_FORMAT_VERSION: u8 = 1;
+
+impl<T: CustomCommand> LogEntry<T> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.term.to_be_bytes());
+        match &self.command {
+            None => bytes.push(NO_COMMAND),
+            Some(command) => write_command(&mut bytes, command),
+        }
+        bytes
+    }
+
+    // A canonical encoding for checksums computed outside the crate: a
+    // version byte followed by the `to_bytes` form, whose id sets are sorted.
+    // It does not change within a major version; `to_json` makes no such
+    // promise. Custom commands are covered only as far as their own
+    // `to_bytes` is stable.
+    pub fn stable_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![STABLE_FORMAT_VERSION];
+        bytes.extend_from_slice(&self.to_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LogEntryError> {
+        let mut reader = Reader(bytes);
+        let term = reader.u64()?;
+        let command = match reader.u8()? {
+            NO_COMMAND => None,
+            tag => Some(reader.command(tag)?),
+        };
+        if !reader.0.is_empty() {
+            return Err(LogEntryError::TrailingBytes(reader.0.len()));
+        }
+        Ok(Self { term, command })
+    }
+}
+
+fn write_command<T: CustomCommand>(bytes: &mut Vec<u8>, command: &Command<T>) {
+    match command {
+        Command::SingleConfiguration{old_configuration, configuration, learners, change_id} => {
+            bytes.push(SINGLE_CONFIGURATION);
+            write_instance_ids(bytes, old_configuration);
+            write_instance_ids(bytes, configuration);
+            write_instance_ids(bytes, learners);
+            write_change_id(bytes, *change_id);
+        },
+        Command::JointConfiguration{old_configuration, new_configuration, change_id} => {
+            bytes.push(JOINT_CONFIGURATION);
+            write_instance_ids(bytes, old_configuration);
+            write_instance_ids(bytes, new_configuration);
+            write_change_id(bytes, *change_id);
+        },
+        Command::Batch(commands) => {
+            bytes.push(BATCH);
+            bytes.extend_from_slice(&(commands.len() as u32).to_be_bytes());
+            for command in commands {
+                write_command(bytes, command);
+            }
+        },
+        Command::NoOp => bytes.push(NO_OP),
+        Command::ClientData(data) => {
+            bytes.push(CLIENT_DATA);
+            bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(data);
+        },
+        Command::Custom(custom_command) => {
+            bytes.push(CUSTOM);
+            let payload = custom_command.to_bytes();
+            bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&payload);
+        },
+    }
+}
+
+fn write_instance_ids(bytes: &mut Vec<u8>, instance_ids: &HashSet<ServerId>) {
+    bytes.extend_from_slice(&(instance_ids.len() as u32).to_be_bytes());
+    for instance_id in sorted_instance_ids(instance_ids) {
+        bytes.extend_from_slice(&instance_id.0.to_be_bytes());
+    }
+}
+
+fn write_change_id(bytes: &mut Vec<u8>, change_id: Option<u64>) {
+    match change_id {
+        Some(change_id) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&change_id.to_be_bytes());
+        },
+        None => bytes.push(0),
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], LogEntryError> {
+        if self.0.len() < length {
+            return Err(LogEntryError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, LogEntryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, LogEntryError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, LogEntryError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn command<T: CustomCommand>(&mut self, tag: u8) -> Result<Command<T>, LogEntryError> {
+        match tag {
+            SINGLE_CONFIGURATION => Ok(Command::SingleConfiguration{
+                old_configuration: self.instance_ids()?,
+                configuration: self.instance_ids()?,
+                learners: self.instance_ids()?,
+                change_id: self.change_id()?,
+            }),
+            JOINT_CONFIGURATION => Ok(Command::JointConfiguration{
+                old_configuration: self.instance_ids()?,
+                new_configuration: self.instance_ids()?,
+                change_id: self.change_id()?,
+            }),
+            BATCH => {
+                let count = self.u32()?;
+                let commands = (0..count)
+                    .map(|_| {
+                        let tag = self.u8()?;
+                        self.command(tag)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Command::batch(commands)
+                    .map_err(|error| LogEntryError::InvalidCustomCommand(error.to_string()))
+            },
+            NO_OP => Ok(Command::NoOp),
+            CLIENT_DATA => {
+                let length = self.u32()? as usize;
+                Ok(Command::ClientData(self.take(length)?.to_vec()))
+            },
+            CUSTOM => {
+                let length = self.u32()? as usize;
+                let payload = self.take(length)?;
+                T::from_bytes(payload)
+                    .map(Command::Custom)
+                    .map_err(|error| LogEntryError::InvalidCustomCommand(error.to_string()))
+            },
+            tag => Err(LogEntryError::UnknownCommandTag(tag)),
+        }
+    }
+
+    fn instance_ids(&mut self) -> Result<HashSet<ServerId>, LogEntryError> {
+        let count = self.u32()?;
+        (0..count).map(|_| self.u64().map(ServerId)).collect()
+    }
+
+    fn change_id(&mut self) -> Result<Option<u64>, LogEntryError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => self.u64().map(Some),
+            flag => Err(LogEntryError::InvalidFlag(flag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{servers, Payload};
+
+    #[test]
+    fn round_trips_every_command_variant() {
+        //Arrange
+        let entries = vec![
+            LogEntry {
+                term: 7,
+                command: None,
+            },
+            LogEntry {
+                term: 7,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(1, 2, 3),
+                    configuration: servers!(1, 2, 3, 4),
+                    learners: servers!(5),
+                    change_id: Some(11),
+                }),
+            },
+            LogEntry {
+                term: 8,
+                command: Some(Command::JointConfiguration{
+                    old_configuration: servers!(1, 2, 3),
+                    new_configuration: servers!(3, 4, 5),
+                    change_id: None,
+                }),
+            },
+            LogEntry {
+                term: 9,
+                command: Some(Command::Custom(Payload(42))),
+            },
+            LogEntry {
+                term: 9,
+                command: Some(Command::Batch(vec![
+                    Command::Custom(Payload(1)),
+                    Command::Custom(Payload(2)),
+                ])),
+            },
+            LogEntry {
+                term: 10,
+                command: Some(Command::NoOp),
+            },
+            LogEntry {
+                term: 10,
+                command: Some(Command::ClientData(vec![0, 255, 7])),
+            },
+        ];
+
+        for entry in entries {
+            //Act
+            let bytes = entry.to_bytes();
+            let decoded = LogEntry::<Payload>::from_bytes(&bytes);
+
+            //Assert
+            assert_eq!(Ok(&entry), decoded.as_ref());
+            assert_eq!(
+                entry.command.as_ref().and_then(Command::change_id),
+                decoded.unwrap().command.as_ref().and_then(Command::change_id)
+            );
+        }
+    }
+
+    #[test]
+    fn encoding_is_independent_of_insertion_order() {
+        //Arrange
+        let mut forward = HashSet::new();
+        let mut backward = HashSet::new();
+        for instance_id in 0..32 {
+            forward.insert(ServerId(instance_id));
+            backward.insert(ServerId(31 - instance_id));
+        }
+        let entry = |configuration| LogEntry::<Payload> {
+            term: 1,
+            command: Some(Command::JointConfiguration{
+                old_configuration: servers!(1),
+                new_configuration: configuration,
+                change_id: None,
+            }),
+        };
+
+        //Assert
+        assert_eq!(entry(forward).to_bytes(), entry(backward).to_bytes());
+    }
+
+    #[test]
+    fn equal_entries_have_equal_stable_bytes() {
+        //Arrange
+        let entry = |configuration| LogEntry::<Payload> {
+            term: 4,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(3, 1, 2),
+                configuration,
+                learners: servers!(),
+                change_id: None,
+            }),
+        };
+
+        //Assert
+        assert_eq!(
+            entry(servers!(1, 2, 3, 4)).stable_bytes(),
+            entry(servers!(4, 3, 2, 1)).stable_bytes()
+        );
+    }
+
+    #[test]
+    fn stable_bytes_format_is_pinned() {
+        //Arrange
+        let entry = LogEntry::<Payload> {
+            term: 2,
+            command: Some(Command::JointConfiguration{
+                old_configuration: servers!(2, 1),
+                new_configuration: servers!(3),
+                change_id: Some(5),
+            }),
+        };
+
+        //Act
+        let bytes = entry.stable_bytes();
+
+        //Assert
+        assert_eq!(
+            vec![
+                1,
+                0, 0, 0, 0, 0, 0, 0, 2,
+                2,
+                0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2,
+                0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 3,
+                1, 0, 0, 0, 0, 0, 0, 0, 5,
+            ],
+            bytes
+        );
+    }
+
+    #[test]
+    fn truncated_bytes_are_rejected() {
+        //Arrange
+        let bytes = LogEntry::<Payload> {
+            term: 2,
+            command: Some(Command::Custom(Payload(1))),
+        }
+        .to_bytes();
+
+        //Act
+        let decoded = LogEntry::<Payload>::from_bytes(&bytes[..bytes.len() - 1]);
+
+        //Assert
+        assert_eq!(Err(LogEntryError::UnexpectedEnd), decoded);
+        assert_eq!(
+            Err(LogEntryError::UnknownCommandTag(9)),
+            LogEntry::<Payload>::from_bytes(&[0, 0, 0, 0, 0, 0, 0, 1, 9])
+        );
+    }
+
+    #[test]
+    fn change_id_flag_other_than_0_or_1_is_rejected() {
+        //Arrange
+        let mut bytes = LogEntry::<Payload> {
+            term: 2,
+            command: Some(Command::JointConfiguration{
+                old_configuration: servers!(1),
+                new_configuration: servers!(2),
+                change_id: None,
+            }),
+        }
+        .to_bytes();
+        *bytes.last_mut().unwrap() = 2;
+
+        //Act
+        let decoded = LogEntry::<Payload>::from_bytes(&bytes);
+
+        //Assert
+        assert_eq!(Err(LogEntryError::InvalidFlag(2)), decoded);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    log_entry::{
+        encode_joint_configuration,
+        encode_single_configuration,
+        sorted_instance_ids,
+        Command,
+        CustomCommand,
+    },
+    server_id::ServerId,
+};
+use serde_json::Value as JsonValue;
+use std::cell::OnceCell;
+
+// Remembers the sorted instance ids of a configuration command between
+// encodings. The command can only be changed through `command_mut`, which
+// forgets the cached ids.
+pub struct CachedCommand<T> {
+    command: Command<T>,
+    sorted_instance_ids: OnceCell<(Vec<ServerId>, Vec<ServerId>)>,
+}
+
+impl<T> CachedCommand<T> {
+    pub fn new(command: Command<T>) -> Self {
+        Self {
+            command,
+            sorted_instance_ids: OnceCell::new(),
+        }
+    }
+
+    pub fn command(&self) -> &Command<T> {
+        &self.command
+    }
+
+    pub fn command_mut(&mut self) -> &mut Command<T> {
+        self.sorted_instance_ids.take();
+        &mut self.command
+    }
+
+    pub fn into_inner(self) -> Command<T> {
+        self.command
+    }
+}
+
+impl<T: CustomCommand> CachedCommand<T> {
+    pub fn to_json(&self) -> JsonValue {
+        match &self.command {
+            Command::SingleConfiguration{old_configuration, configuration, learners, change_id} => {
+                let (old_configuration, configuration) = self.sorted_instance_ids.get_or_init(|| {
+                    (sorted_instance_ids(old_configuration), sorted_instance_ids(configuration))
+                });
+                encode_single_configuration(
+                    old_configuration,
+                    configuration,
+                    &sorted_instance_ids(learners),
+                    *change_id,
+                )
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, change_id} => {
+                let (old_configuration, new_configuration) = self.sorted_instance_ids.get_or_init(|| {
+                    (sorted_instance_ids(old_configuration), sorted_instance_ids(new_configuration))
+                });
+                encode_joint_configuration(old_configuration, new_configuration, *change_id)
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => {
+                self.command.to_json()
+            },
+        }
+    }
+}
+
+impl<T> From<Command<T>> for CachedCommand<T> {
+    fn from(command: Command<T>) -> Self {
+        Self::new(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{servers, Payload};
+
+    #[test]
+    fn cached_encoding_matches_command_encoding() {
+        //Arrange
+        let command = Command::<Payload>::JointConfiguration{
+            old_configuration: servers!(8354, 5, 42),
+            new_configuration: servers!(13531, 42, 85),
+            change_id: None,
+        };
+        let expected = command.to_json();
+        let cached = CachedCommand::new(command);
+
+        //Act
+        let first = cached.to_json();
+        let second = cached.to_json();
+
+        //Assert
+        assert_eq!(expected, first);
+        assert_eq!(expected, second);
+    }
+
+    #[test]
+    fn cache_is_invalidated_after_mutation() {
+        //Arrange
+        let mut cached = CachedCommand::new(Command::<Payload>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: None,
+        });
+        cached.to_json();
+
+        //Act
+        if let Command::SingleConfiguration{configuration, ..} = cached.command_mut() {
+            configuration.insert(ServerId(4));
+            configuration.remove(&ServerId(1));
+        }
+
+        //Assert
+        assert_eq!(cached.command().to_json(), cached.to_json());
+        assert_eq!(
+            serde_json::json!([2, 3, 4]),
+            cached.to_json()["configuration"]["instanceIds"]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+// A recovery file may hold snapshots and entries side by side, one JSON
+// record per line: `{"snapshot": {...}}` or `{"entry": {...}}`. A snapshot
+// record carries `lastIncludedIndex`, `lastIncludedTerm`, the
+// `configuration` as an array of ids and the state as base64 `data`; the
+// entries after it continue from its last included index.
+
+use crate::{
+    decode::DecodeOptions,
+    log_entry::{CustomCommand, LogEntry},
+    snapshot::{decode_snapshot, SnapshotMeta},
+};
+use serde_json::Value as JsonValue;
+use std::{
+    io::{self, BufRead, Lines},
+    marker::PhantomData,
+};
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Record<T> {
+    Snapshot(SnapshotMeta, Vec<u8>),
+    Entry(LogEntry<T>),
+}
+
+pub struct CombinedStream<R, T> {
+    lines: Lines<R>,
+    next_index: usize,
+    marker: PhantomData<T>,
+}
+
+pub fn read_combined_stream<R: BufRead, T: CustomCommand>(reader: R) -> CombinedStream<R, T> {
+    CombinedStream {
+        lines: reader.lines(),
+        next_index: 1,
+        marker: PhantomData,
+    }
+}
+
+impl<R, T> CombinedStream<R, T> {
+    // The index the next entry record will take: one past the last entry
+    // read, or past the last snapshot if no entry has followed it.
+    pub fn next_index(&self) -> usize {
+        self.next_index
+    }
+}
+
+impl<R: BufRead, T: CustomCommand> Iterator for CombinedStream<R, T> {
+    type Item = io::Result<Record<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = loop {
+            match self.lines.next()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                line => break line,
+            }
+        };
+        Some(line.and_then(|line| {
+            let json: JsonValue = serde_json::from_str(&line).map_err(invalid_data)?;
+            let record = decode_record(&json)?;
+            self.next_index = match &record {
+                Record::Snapshot(meta, _) => meta.last_included_index + 1,
+                Record::Entry(_) => self.next_index + 1,
+            };
+            Ok(record)
+        }))
+    }
+}
+
+fn decode_record<T: CustomCommand>(json: &JsonValue) -> io::Result<Record<T>> {
+    if let Some(entry) = json.get("entry") {
+        return LogEntry::decode(entry, &DecodeOptions::default())
+            .map(Record::Entry)
+            .map_err(invalid_data);
+    }
+    let snapshot = json
+        .get("snapshot")
+        .ok_or_else(|| invalid_data("record is neither a snapshot nor an entry"))?;
+    let (meta, data) = decode_snapshot(snapshot).map_err(invalid_data)?;
+    Ok(Record::Snapshot(meta, data))
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::Command,
+        testing::{servers, Payload},
+    };
+    use std::io::Cursor;
+
+    fn entry(term: u64, value: usize) -> LogEntry<Payload> {
+        LogEntry {
+            term,
+            command: Some(Command::Custom(Payload(value))),
+        }
+    }
+
+    fn entry_line(term: u64, value: usize) -> String {
+        serde_json::json!({"entry": entry(term, value).to_json()}).to_string()
+    }
+
+    #[test]
+    fn snapshot_resets_the_next_index() {
+        //Arrange
+        let file = [
+            entry_line(1, 1),
+            serde_json::json!({
+                "snapshot": {
+                    "lastIncludedIndex": 10,
+                    "lastIncludedTerm": 2,
+                    "configuration": [1, 2, 3],
+                    "data": "c3RhdGU=",
+                },
+            })
+            .to_string(),
+            entry_line(2, 11),
+            String::new(),
+            entry_line(3, 12),
+        ]
+        .join("\n");
+        let mut stream = read_combined_stream::<_, Payload>(Cursor::new(file));
+
+        //Act
+        let mut indexed = Vec::new();
+        while let Some(record) = stream.next() {
+            let index = stream.next_index() - 1;
+            indexed.push((index, record.unwrap()));
+        }
+
+        //Assert
+        assert_eq!(
+            vec![
+                (1, Record::Entry(entry(1, 1))),
+                (
+                    10,
+                    Record::Snapshot(
+                        SnapshotMeta {
+                            last_included_index: 10,
+                            last_included_term: 2,
+                            configuration: servers!(1, 2, 3),
+                        },
+                        b"state".to_vec()
+                    )
+                ),
+                (11, Record::Entry(entry(2, 11))),
+                (12, Record::Entry(entry(3, 12))),
+            ],
+            indexed
+        );
+        assert_eq!(13, stream.next_index());
+    }
+
+    #[test]
+    fn malformed_records_are_invalid_data() {
+        //Arrange
+        let file = "{\"snapshot\": {\"lastIncludedIndex\": 4}}\n{\"other\": {}}\nnot json";
+
+        //Act
+        let errors = read_combined_stream::<_, Payload>(Cursor::new(file))
+            .map(|record| record.unwrap_err().kind())
+            .collect::<Vec<_>>();
+
+        //Assert
+        assert_eq!(vec![io::ErrorKind::InvalidData; 3], errors);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    log_entry::{Command, LogEntry},
+    server_id::ServerId,
+    state_machine::StateMachine,
+};
+use std::{
+    collections::HashSet,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigChanged {
+    pub index: usize,
+    pub old_voters: HashSet<ServerId>,
+    pub new_voters: HashSet<ServerId>,
+}
+
+// Wraps a state machine and notifies subscribers whenever a configuration
+// entry is applied through it. For a joint configuration the new voters are
+// those of the configuration being transitioned to.
+pub struct ConfigWatcher<SM> {
+    state_machine: SM,
+    subscribers: Vec<Sender<ConfigChanged>>,
+}
+
+impl<SM> ConfigWatcher<SM> {
+    pub fn new(state_machine: SM) -> Self {
+        Self {
+            state_machine,
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<ConfigChanged> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn state_machine(&self) -> &SM {
+        &self.state_machine
+    }
+
+    pub fn into_inner(self) -> SM {
+        self.state_machine
+    }
+}
+
+impl<T, SM: StateMachine<T>> StateMachine<T> for ConfigWatcher<SM> {
+    type Response = SM::Response;
+
+    fn apply(&mut self, index: usize, entry: &LogEntry<T>) -> SM::Response {
+        let change = match &entry.command {
+            Some(Command::SingleConfiguration{old_configuration, configuration, ..}) => Some(ConfigChanged {
+                index,
+                old_voters: old_configuration.clone(),
+                new_voters: configuration.clone(),
+            }),
+            Some(Command::JointConfiguration{old_configuration, new_configuration, ..}) => Some(ConfigChanged {
+                index,
+                old_voters: old_configuration.clone(),
+                new_voters: new_configuration.clone(),
+            }),
+            _ => None,
+        };
+        if let Some(change) = change {
+            // Subscribers that hung up are dropped rather than treated as errors.
+            self.subscribers
+                .retain(|subscriber| subscriber.send(change.clone()).is_ok());
+        }
+        self.state_machine.apply(index, entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log::Log,
+        testing::{servers, Payload},
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        applied: Vec<usize>,
+    }
+
+    impl StateMachine<Payload> for Recorder {
+        type Response = ();
+
+        fn apply(&mut self, index: usize, _entry: &LogEntry<Payload>) {
+            self.applied.push(index);
+        }
+    }
+
+    #[test]
+    fn emits_event_for_each_applied_configuration() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(LogEntry {
+            term: 1,
+            command: Some(Command::Custom(Payload(1))),
+        });
+        log.append(LogEntry {
+            term: 1,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 2, 3, 4),
+                learners: servers!(),
+                change_id: None,
+            }),
+        });
+        log.append(LogEntry {
+            term: 2,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3, 4),
+                configuration: servers!(2, 3, 4),
+                learners: servers!(),
+                change_id: None,
+            }),
+        });
+        let mut watcher = ConfigWatcher::new(Recorder::default());
+        let events = watcher.subscribe();
+
+        //Act
+        log.apply_committed(&mut watcher, 3);
+
+        //Assert
+        assert_eq!(
+            vec![
+                ConfigChanged {
+                    index: 2,
+                    old_voters: servers!(1, 2, 3),
+                    new_voters: servers!(1, 2, 3, 4),
+                },
+                ConfigChanged {
+                    index: 3,
+                    old_voters: servers!(1, 2, 3, 4),
+                    new_voters: servers!(2, 3, 4),
+                },
+            ],
+            events.try_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(vec![1, 2, 3], watcher.into_inner().applied);
+    }
+}
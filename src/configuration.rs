@@ -0,0 +1,121 @@
+use crate::{
+    log_entry::{Command, ConfigError},
+    quorum::majority,
+    server_id::ServerId,
+};
+use std::collections::HashSet;
+
+// The voters in effect, as set by the latest configuration entry. A change
+// of more than one server goes through a joint configuration, in which
+// decisions need a majority of the old voters and of the new ones.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Configuration {
+    Single(HashSet<ServerId>),
+    Joint{
+        old: HashSet<ServerId>,
+        new: HashSet<ServerId>,
+    },
+}
+
+impl Configuration {
+    // The configuration `command` puts into effect, if it is a configuration
+    // command.
+    pub fn from_command<T>(command: &Command<T>) -> Option<Self> {
+        match command {
+            Command::SingleConfiguration{configuration, ..} => Some(Configuration::Single(configuration.clone())),
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => Some(Configuration::Joint{
+                old: old_configuration.clone(),
+                new: new_configuration.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    // The first step of a change from `old` to `new`.
+    pub fn enter_joint<T>(old: &HashSet<ServerId>, new: &HashSet<ServerId>) -> Command<T> {
+        Command::JointConfiguration{
+            old_configuration: old.clone(),
+            new_configuration: new.clone(),
+            change_id: None,
+        }
+    }
+
+    // The second step, appended once `joint` has committed, which leaves
+    // only the new voters in charge. It carries the joint entry's change id.
+    pub fn leave_joint<T>(joint: &Command<T>) -> Result<Command<T>, ConfigError> {
+        match joint {
+            Command::JointConfiguration{old_configuration, new_configuration, change_id} => Ok(Command::SingleConfiguration{
+                old_configuration: old_configuration.clone(),
+                configuration: new_configuration.clone(),
+                learners: HashSet::new(),
+                change_id: *change_id,
+            }),
+            _ => Err(ConfigError::NotJoint),
+        }
+    }
+
+    // Whether `id` votes, which during a joint configuration includes the
+    // members of both sets.
+    pub fn is_voter(&self, id: ServerId) -> bool {
+        match self {
+            Configuration::Single(voters) => voters.contains(&id),
+            Configuration::Joint{old, new} => old.contains(&id) || new.contains(&id),
+        }
+    }
+
+    // Whether `votes` are enough to elect a leader or commit an entry: a
+    // majority of the voters, or during a joint configuration a majority of
+    // each set.
+    pub fn has_quorum(&self, votes: &HashSet<ServerId>) -> bool {
+        let has_majority = |voters: &HashSet<ServerId>| voters.intersection(votes).count() >= majority(voters.len());
+        match self {
+            Configuration::Single(voters) => has_majority(voters),
+            Configuration::Joint{old, new} => has_majority(old) && has_majority(new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::servers;
+
+    #[test]
+    fn walks_a_cluster_through_the_joint_phase() {
+        //Arrange
+        let old = servers!(1, 2, 3);
+        let new = servers!(3, 4, 5);
+
+        //Act
+        let joint = Configuration::enter_joint::<()>(&old, &new);
+        let single = Configuration::leave_joint(&joint).unwrap();
+        let during = Configuration::from_command(&joint).unwrap();
+        let after = Configuration::from_command(&single).unwrap();
+
+        //Assert
+        assert_eq!(Configuration::Joint{old: old.clone(), new: new.clone()}, during);
+        assert_eq!(Configuration::Single(new), after);
+        assert!((1..=5).all(|id| during.is_voter(ServerId(id))));
+        assert!(!after.is_voter(ServerId(1)));
+        assert!(after.is_voter(ServerId(4)));
+        assert!(!during.has_quorum(&servers!(1, 2, 3)));
+        assert!(!during.has_quorum(&servers!(3, 4, 5)));
+        assert!(during.has_quorum(&servers!(1, 3, 4)));
+        assert!(after.has_quorum(&servers!(3, 4)));
+    }
+
+    #[test]
+    fn only_a_joint_configuration_can_be_left() {
+        //Arrange
+        let single = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+
+        //Assert
+        assert_eq!(Err(ConfigError::NotJoint), Configuration::leave_joint(&single));
+        assert_eq!(None, Configuration::from_command(&Command::Custom(())));
+    }
+}
@@ -0,0 +1,978 @@
+use crate::{
+    log_entry::{decode_change_id, Command, CommandDecodeError, CustomCommand, LogEntry},
+    server_id::ServerId,
+};
+use serde_json::Value as JsonValue;
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    io::BufRead,
+    marker::PhantomData,
+    ops::RangeInclusive,
+};
+use thiserror::Error;
+
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    // Accept terms encoded as JSON strings, such as `"term": "9"`.
+    pub coerce_string_term: bool,
+    // The configuration preceding the entry, used to rebuild single
+    // configurations written with `EncodeOptions::delta_config`.
+    pub delta_config: Option<HashSet<ServerId>>,
+    // Reject configurations that are not shaped `{"instanceIds": [...]}`
+    // instead of decoding them as empty.
+    pub strict_configuration: bool,
+    // Reject entries whose JSON nests objects and arrays deeper than this,
+    // counting the entry object itself as depth 1.
+    pub max_depth: Option<usize>,
+    // Reject entries without a term instead of decoding them with term 0.
+    pub require_term: bool,
+    // Reject configurations naming a server id outside this range.
+    pub id_range: Option<RangeInclusive<ServerId>>,
+    // Also read configurations written as `{"members": [...]}` by older
+    // versions. `instanceIds` wins when both are present.
+    pub accept_members_key: bool,
+}
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum LogEntryError {
+    #[error("invalid term {0}")]
+    InvalidTerm(String),
+    #[error("delta-encoded configuration cannot be decoded without the prior configuration")]
+    MissingPriorConfiguration,
+    #[error("`{field}` is not a configuration object")]
+    MalformedConfiguration{
+        field: &'static str,
+    },
+    #[error("entry is nested more than {0} levels deep")]
+    TooDeep(usize),
+    #[error("invalid JSON: {0}")]
+    InvalidJson(String),
+    #[error("missing field `{0}`")]
+    MissingField(&'static str),
+    #[error("field `{field}` has unrecognized value {value}")]
+    InvalidValue{
+        field: &'static str,
+        value: String,
+    },
+    #[error("invalid instance id at {pointer}")]
+    InvalidInstanceId{
+        pointer: String,
+    },
+    #[error("instance id {0} is outside the allowed range")]
+    IdOutOfRange(ServerId),
+    #[error("entry bytes end unexpectedly")]
+    UnexpectedEnd,
+    #[error("unknown command tag {0}")]
+    UnknownCommandTag(u8),
+    #[error("presence flag is {0}, not 0 or 1")]
+    InvalidFlag(u8),
+    #[error("{0} bytes left over after the entry")]
+    TrailingBytes(usize),
+    #[error("invalid custom command: {0}")]
+    InvalidCustomCommand(String),
+    #[error("invalid command: {0}")]
+    InvalidCommand(String),
+    #[error("entry with a command has term 0, which is reserved for the empty log")]
+    CommandAtTermZero,
+    #[error("snapshot ends at index {snapshot_index} term {snapshot_term}, but the log is based at index {log_index} term {log_term}")]
+    SnapshotLogMismatch{
+        snapshot_index: usize,
+        snapshot_term: u64,
+        log_index: usize,
+        log_term: u64,
+    },
+    #[error("line is longer than {0} bytes")]
+    LineTooLong(usize),
+    #[error("reading the log failed: {0}")]
+    Io(String),
+}
+
+impl LogEntryError {
+    // The JSON pointer (RFC 6901) of the value that failed to decode, where
+    // the error concerns a single value.
+    pub fn pointer(&self) -> Option<String> {
+        match self {
+            LogEntryError::InvalidTerm(_) | LogEntryError::CommandAtTermZero => Some(String::from("/term")),
+            LogEntryError::MissingField(field @ "newConfiguration") => Some(format!("/command/{}", field)),
+            LogEntryError::MissingField(field) | LogEntryError::InvalidValue{field, ..} => Some(format!("/{}", field)),
+            LogEntryError::MalformedConfiguration{field: "command"} => Some(String::from("/command")),
+            LogEntryError::MalformedConfiguration{field} => Some(format!("/command/{}", field)),
+            LogEntryError::InvalidInstanceId{pointer} => Some(pointer.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: CustomCommand> LogEntry<T> {
+    // Checks the nesting depth before handing the bytes to the JSON parser,
+    // so an overly deep entry is rejected without being parsed at all.
+    pub fn decode_slice(bytes: &[u8], options: &DecodeOptions) -> Result<Self, LogEntryError> {
+        if let Some(max_depth) = options.max_depth {
+            if encoded_depth_exceeds(bytes, max_depth) {
+                return Err(LogEntryError::TooDeep(max_depth));
+            }
+        }
+        let json = serde_json::from_slice(bytes)
+            .map_err(|error| LogEntryError::InvalidJson(error.to_string()))?;
+        Self::decode(&json, options)
+    }
+
+    pub fn decode(json: &JsonValue, options: &DecodeOptions) -> Result<Self, LogEntryError> {
+        if let Some(max_depth) = options.max_depth {
+            if depth_exceeds(json, max_depth) {
+                return Err(LogEntryError::TooDeep(max_depth));
+            }
+        }
+        let entry = Self {
+            term: decode_term(json, options)?,
+            command: decode_command(json, options)?,
+        };
+        entry.validate()?;
+        Ok(entry)
+    }
+}
+
+fn decode_command<T: CustomCommand>(
+    json: &JsonValue,
+    options: &DecodeOptions,
+) -> Result<Option<Command<T>>, LogEntryError> {
+    let command_type = json.get("type").and_then(JsonValue::as_str);
+    match (command_type, json.get("command")) {
+        (Some("SingleConfiguration"), command) => decode_single_configuration(configuration_body(command)?, options).map(Some),
+        (Some("JointConfiguration"), command) => decode_joint_configuration(configuration_body(command)?, options).map(Some),
+        _ => match Command::try_from(json) {
+            Ok(command) => Ok(Some(command)),
+            Err(CommandDecodeError::MissingType) => Ok(None),
+            Err(error) => Err(LogEntryError::InvalidCommand(error.to_string())),
+        },
+    }
+}
+
+// A configuration command must have an object body, whatever the options,
+// as `Command::try_from` requires.
+fn configuration_body(command: Option<&JsonValue>) -> Result<&JsonValue, LogEntryError> {
+    command
+        .filter(|command| command.is_object())
+        .ok_or(LogEntryError::MalformedConfiguration{field: "command"})
+}
+
+// The configuration commands are decoded here for both `LogEntry::decode`
+// and `Command::try_from`, so the two accept the same configurations.
+pub(crate) fn decode_single_configuration<T>(
+    command: &JsonValue,
+    options: &DecodeOptions,
+) -> Result<Command<T>, LogEntryError> {
+    if is_delta(command) {
+        let prior = options
+            .delta_config
+            .as_ref()
+            .ok_or(LogEntryError::MissingPriorConfiguration)?;
+        let mut configuration = prior.clone();
+        for instance_id in decode_id_array(command.get("remove"), "/command/remove", options)? {
+            configuration.remove(&instance_id);
+        }
+        configuration.extend(decode_id_array(command.get("add"), "/command/add", options)?);
+        return Ok(Command::SingleConfiguration{
+            old_configuration: prior.clone(),
+            configuration,
+            learners: decode_configuration(command, "learners", options)?,
+            change_id: decode_change_id(command),
+        });
+    }
+    Ok(Command::SingleConfiguration{
+        old_configuration: decode_configuration(command, "oldConfiguration", options)?,
+        configuration: decode_configuration(command, "configuration", options)?,
+        learners: decode_configuration(command, "learners", options)?,
+        change_id: decode_change_id(command),
+    })
+}
+
+// A joint configuration without a target has nothing to transition to, so
+// it is rejected rather than decoded with an empty new set.
+pub(crate) fn decode_joint_configuration<T>(
+    command: &JsonValue,
+    options: &DecodeOptions,
+) -> Result<Command<T>, LogEntryError> {
+    if command.get("newConfiguration").is_none() {
+        return Err(LogEntryError::MissingField("newConfiguration"));
+    }
+    Ok(Command::JointConfiguration{
+        old_configuration: decode_configuration(command, "oldConfiguration", options)?,
+        new_configuration: decode_configuration(command, "newConfiguration", options)?,
+        change_id: decode_change_id(command),
+    })
+}
+
+// The key holding a configuration's ids. Earlier versions read and wrote the
+// misspelled `instaceIds`, so logs they left behind are still accepted.
+fn instance_ids_key(configuration: &JsonValue) -> Option<&'static str> {
+    ["instanceIds", "instaceIds"]
+        .into_iter()
+        .find(|&key| configuration.get(key).is_some())
+}
+
+fn decode_configuration(
+    command: &JsonValue,
+    field: &'static str,
+    options: &DecodeOptions,
+) -> Result<HashSet<ServerId>, LogEntryError> {
+    match command.get(field) {
+        Some(configuration @ JsonValue::Object(_)) => {
+            let key = match instance_ids_key(configuration) {
+                Some(key) => key,
+                None if options.accept_members_key => "members",
+                None => "instanceIds",
+            };
+            let instance_ids = configuration.get(key);
+            if options.strict_configuration && matches!(instance_ids, Some(ids) if !ids.is_array()) {
+                return Err(LogEntryError::MalformedConfiguration{field});
+            }
+            decode_id_array(instance_ids, &format!("/command/{}/{}", field, key), options)
+        },
+        Some(_) if options.strict_configuration => Err(LogEntryError::MalformedConfiguration{field}),
+        _ => Ok(HashSet::new()),
+    }
+}
+
+fn is_delta(command: &JsonValue) -> bool {
+    command.get("add").is_some() || command.get("remove").is_some()
+}
+
+fn decode_id_array(
+    instance_ids: Option<&JsonValue>,
+    pointer: &str,
+    options: &DecodeOptions,
+) -> Result<HashSet<ServerId>, LogEntryError> {
+    instance_ids
+        .and_then(JsonValue::as_array)
+        .map(|instance_ids| {
+            instance_ids
+                .iter()
+                .enumerate()
+                .map(|(position, instance_id)| {
+                    let instance_id = instance_id
+                        .as_u64()
+                        .map(ServerId)
+                        .ok_or_else(|| LogEntryError::InvalidInstanceId{
+                            pointer: format!("{}/{}", pointer, position),
+                        })?;
+                    match &options.id_range {
+                        Some(id_range) if !id_range.contains(&instance_id) => {
+                            Err(LogEntryError::IdOutOfRange(instance_id))
+                        },
+                        _ => Ok(instance_id),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_else(|| Ok(HashSet::new()))
+}
+
+fn encoded_depth_exceeds(bytes: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &byte in bytes {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            },
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    false
+}
+
+// Walks the value without recursion so that checking a deep value cannot
+// itself exhaust the stack.
+fn depth_exceeds(json: &JsonValue, max_depth: usize) -> bool {
+    let mut pending = vec![(json, 1)];
+    while let Some((value, depth)) = pending.pop() {
+        match value {
+            JsonValue::Array(_) | JsonValue::Object(_) if depth > max_depth => return true,
+            JsonValue::Array(values) => pending.extend(values.iter().map(|child| (child, depth + 1))),
+            JsonValue::Object(values) => pending.extend(values.values().map(|child| (child, depth + 1))),
+            _ => (),
+        }
+    }
+    false
+}
+
+fn decode_term(json: &JsonValue, options: &DecodeOptions) -> Result<u64, LogEntryError> {
+    match json.get("term") {
+        None if options.require_term => Err(LogEntryError::MissingField("term")),
+        None => Ok(0),
+        Some(JsonValue::Number(term)) => term
+            .as_u64()
+            .ok_or_else(|| LogEntryError::InvalidTerm(term.to_string())),
+        Some(JsonValue::String(term)) if options.coerce_string_term => term
+            .parse()
+            .map_err(|_| LogEntryError::InvalidTerm(format!("{:?}", term))),
+        Some(term) => Err(LogEntryError::InvalidTerm(term.to_string())),
+    }
+}
+
+// Decodes a log written one JSON entry per line, skipping blank lines. No
+// more than `max_line_bytes` of a line are ever buffered: a longer line is
+// skipped and reported as `LineTooLong`, and decoding carries on with the
+// next one. A read error ends the stream.
+pub fn decode_log_stream<R: BufRead, T: CustomCommand>(
+    reader: R,
+    options: DecodeOptions,
+    max_line_bytes: usize,
+) -> DecodeLogStream<R, T> {
+    DecodeLogStream {
+        reader,
+        options,
+        max_line_bytes,
+        failed: false,
+        _command: PhantomData,
+    }
+}
+
+pub struct DecodeLogStream<R, T> {
+    reader: R,
+    options: DecodeOptions,
+    max_line_bytes: usize,
+    failed: bool,
+    _command: PhantomData<T>,
+}
+
+impl<R: BufRead, T> DecodeLogStream<R, T> {
+    // Reads up to the next newline, keeping at most `max_line_bytes` of it.
+    // Returns `None` at the end of the input.
+    fn read_line(&mut self) -> Option<Result<Vec<u8>, LogEntryError>> {
+        let mut line = Vec::new();
+        let mut too_long = false;
+        let mut read_any = false;
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(error) => return Some(Err(LogEntryError::Io(error.to_string()))),
+            };
+            if buffer.is_empty() {
+                break;
+            }
+            read_any = true;
+            let newline = buffer.iter().position(|&byte| byte == b'\n');
+            let taken = newline.unwrap_or(buffer.len());
+            if line.len() + taken > self.max_line_bytes {
+                too_long = true;
+                line.clear();
+            } else if !too_long {
+                line.extend_from_slice(&buffer[..taken]);
+            }
+            self.reader.consume(taken + newline.map_or(0, |_| 1));
+            if newline.is_some() {
+                break;
+            }
+        }
+        match (read_any, too_long) {
+            (false, _) => None,
+            (true, true) => Some(Err(LogEntryError::LineTooLong(self.max_line_bytes))),
+            (true, false) => Some(Ok(line)),
+        }
+    }
+}
+
+impl<R: BufRead, T: CustomCommand> Iterator for DecodeLogStream<R, T> {
+    type Item = Result<LogEntry<T>, LogEntryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            return match self.read_line()? {
+                Ok(line) if line.iter().all(u8::is_ascii_whitespace) => continue,
+                Ok(line) => Some(LogEntry::decode_slice(&line, &self.options)),
+                Err(error @ LogEntryError::Io(_)) => {
+                    self.failed = true;
+                    Some(Err(error))
+                },
+                Err(error) => Some(Err(error)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{servers, Payload};
+    use serde_json::json;
+    use std::io::{BufReader, Cursor};
+
+    fn lenient() -> DecodeOptions {
+        DecodeOptions {
+            coerce_string_term: true,
+            ..DecodeOptions::default()
+        }
+    }
+
+    #[test]
+    fn string_term_is_coerced_in_lenient_mode() {
+        //Arrange
+        let json = json!({
+            "term": "9",
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &lenient());
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 9,
+                command: None,
+            }),
+            entry
+        );
+    }
+
+    #[test]
+    fn string_term_is_rejected_in_strict_mode() {
+        //Arrange
+        let json = json!({
+            "term": "9",
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(Err(LogEntryError::InvalidTerm(String::from("\"9\""))), entry);
+    }
+
+    #[test]
+    fn legacy_misspelled_instance_ids_are_decoded() {
+        //Arrange
+        let json = json!({
+            "term": 9,
+            "type": "SingleConfiguration",
+            "command": {
+                "oldConfiguration": {"instaceIds": [1, 2, 3]},
+                "configuration": {"instanceIds": [1, 2], "instaceIds": [4]},
+            },
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &DecodeOptions::default()).unwrap();
+
+        //Assert
+        assert_eq!(
+            Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 2),
+                learners: servers!(),
+                change_id: None,
+            }),
+            entry.command
+        );
+    }
+
+    #[test]
+    fn corrupt_command_is_rejected() {
+        //Arrange
+        let json = json!({
+            "term": 9,
+            "type": "ClientData",
+            "command": {"data": "not base64!"},
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(
+            Err(LogEntryError::InvalidCommand(String::from("client data is not valid base64"))),
+            entry
+        );
+    }
+
+    #[test]
+    fn non_numeric_string_term_is_rejected_in_both_modes() {
+        //Arrange
+        let json = json!({
+            "term": "nine",
+        });
+
+        //Act
+        let strict = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+        let lenient = LogEntry::<Payload>::decode(&json, &lenient());
+
+        //Assert
+        assert!(matches!(strict, Err(LogEntryError::InvalidTerm(_))));
+        assert!(matches!(lenient, Err(LogEntryError::InvalidTerm(_))));
+    }
+
+    #[test]
+    fn bare_array_configuration_is_rejected_in_strict_mode() {
+        //Arrange
+        let json = json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": {
+                "oldConfiguration": {
+                    "instanceIds": [1, 2],
+                },
+                "configuration": [1, 2, 3],
+            },
+        });
+        let strict = DecodeOptions {
+            strict_configuration: true,
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &strict);
+
+        //Assert
+        assert_eq!(
+            Err(LogEntryError::MalformedConfiguration{field: "configuration"}),
+            entry
+        );
+    }
+
+    #[test]
+    fn non_array_instance_ids_are_rejected_in_strict_mode() {
+        //Arrange
+        let json = json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": {
+                "configuration": {
+                    "instanceIds": "nope",
+                },
+            },
+        });
+        let strict = DecodeOptions {
+            strict_configuration: true,
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &strict);
+
+        //Assert
+        assert_eq!(
+            Err(LogEntryError::MalformedConfiguration{field: "configuration"}),
+            entry
+        );
+    }
+
+    #[test]
+    fn non_object_configuration_command_is_rejected() {
+        //Arrange
+        let json = json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": "oops",
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(Err(LogEntryError::MalformedConfiguration{field: "command"}), entry);
+        assert_eq!(Some(String::from("/command")), entry.unwrap_err().pointer());
+        assert!(matches!(
+            Command::<Payload>::try_from(&json),
+            Err(CommandDecodeError::MalformedConfiguration)
+        ));
+    }
+
+    #[test]
+    fn well_formed_configuration_is_accepted_in_strict_mode() {
+        //Arrange
+        let json = json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": {
+                "oldConfiguration": {
+                    "instanceIds": [1, 2],
+                },
+                "configuration": {
+                    "instanceIds": [1, 2, 3],
+                },
+            },
+        });
+        let strict = DecodeOptions {
+            strict_configuration: true,
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &strict);
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 2,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(1, 2),
+                    configuration: servers!(1, 2, 3),
+                    learners: servers!(),
+                    change_id: None,
+                }),
+            }),
+            entry
+        );
+    }
+
+    fn nested_payload(depth: usize) -> String {
+        format!(
+            r#"{{"type": "Payload", "term": 1, "command": {{"value": 1, "nested": {}{}}}}}"#,
+            "[".repeat(depth),
+            "]".repeat(depth)
+        )
+    }
+
+    #[test]
+    fn deeply_nested_payload_is_rejected() {
+        //Arrange
+        let options = DecodeOptions {
+            max_depth: Some(32),
+            ..DecodeOptions::default()
+        };
+        let encoded = nested_payload(100);
+
+        //Act
+        let from_slice = LogEntry::<Payload>::decode_slice(encoded.as_bytes(), &options);
+        let from_value = LogEntry::<Payload>::decode(
+            &serde_json::from_str(&encoded).unwrap(),
+            &options
+        );
+
+        //Assert
+        assert_eq!(Err(LogEntryError::TooDeep(32)), from_slice);
+        assert_eq!(Err(LogEntryError::TooDeep(32)), from_value);
+    }
+
+    #[test]
+    fn payload_within_depth_limit_is_accepted() {
+        //Arrange
+        let options = DecodeOptions {
+            max_depth: Some(32),
+            ..DecodeOptions::default()
+        };
+        let encoded = nested_payload(5);
+
+        //Act
+        let entry = LogEntry::<Payload>::decode_slice(encoded.as_bytes(), &options);
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 1,
+                command: Some(Command::Custom(Payload(1))),
+            }),
+            entry
+        );
+        assert!(!encoded_depth_exceeds(br#"{"a": "[[[[[[[["}"#, 2));
+    }
+
+    #[test]
+    fn error_points_at_invalid_instance_id() {
+        //Arrange
+        let json = json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": {
+                "oldConfiguration": {
+                    "instanceIds": [1, 2],
+                },
+                "configuration": {
+                    "instanceIds": [1, 2, "three"],
+                },
+            },
+        });
+
+        //Act
+        let error = LogEntry::<Payload>::decode(&json, &DecodeOptions::default()).unwrap_err();
+
+        //Assert
+        assert_eq!(
+            Some(String::from("/command/configuration/instanceIds/2")),
+            error.pointer()
+        );
+        assert_eq!(
+            Some(&json!("three")),
+            json.pointer(&error.pointer().unwrap())
+        );
+    }
+
+    #[test]
+    fn missing_term_decodes_as_zero_by_default() {
+        //Arrange
+        let json = json!({});
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 0,
+                command: None,
+            }),
+            entry
+        );
+    }
+
+    #[test]
+    fn missing_term_is_rejected_when_required() {
+        //Arrange
+        let json = json!({});
+        let options = DecodeOptions {
+            require_term: true,
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &options);
+
+        //Assert
+        assert_eq!(Err(LogEntryError::MissingField("term")), entry);
+    }
+
+    #[test]
+    fn joint_configuration_without_new_configuration_is_rejected() {
+        //Arrange
+        let json = json!({
+            "type": "JointConfiguration",
+            "term": 3,
+            "command": {
+                "oldConfiguration": {
+                    "instanceIds": [1, 2, 3],
+                },
+            },
+        });
+
+        //Act
+        let error = LogEntry::<Payload>::decode(&json, &DecodeOptions::default()).unwrap_err();
+
+        //Assert
+        assert_eq!(LogEntryError::MissingField("newConfiguration"), error);
+        assert_eq!(Some(String::from("/command/newConfiguration")), error.pointer());
+    }
+
+    #[test]
+    fn well_formed_joint_configuration_is_accepted() {
+        //Arrange
+        let json = json!({
+            "type": "JointConfiguration",
+            "term": 3,
+            "command": {
+                "oldConfiguration": {
+                    "instanceIds": [1, 2, 3],
+                },
+                "newConfiguration": {
+                    "instanceIds": [2, 3, 4],
+                },
+            },
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 3,
+                command: Some(Command::JointConfiguration{
+                    old_configuration: servers!(1, 2, 3),
+                    new_configuration: servers!(2, 3, 4),
+                    change_id: None,
+                }),
+            }),
+            entry
+        );
+    }
+
+    fn configuration_with_ids(instance_ids: &[usize]) -> JsonValue {
+        json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": {
+                "oldConfiguration": {
+                    "instanceIds": [1, 2],
+                },
+                "configuration": {
+                    "instanceIds": instance_ids,
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn ids_within_range_are_accepted() {
+        //Arrange
+        let options = DecodeOptions {
+            id_range: Some(ServerId(1)..=ServerId(5)),
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&configuration_with_ids(&[1, 2, 5]), &options);
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 2,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(1, 2),
+                    configuration: servers!(1, 2, 5),
+                    learners: servers!(),
+                    change_id: None,
+                }),
+            }),
+            entry
+        );
+    }
+
+    #[test]
+    fn id_outside_range_is_rejected() {
+        //Arrange
+        let options = DecodeOptions {
+            id_range: Some(ServerId(1)..=ServerId(5)),
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&configuration_with_ids(&[1, 2, 6]), &options);
+
+        //Assert
+        assert_eq!(Err(LogEntryError::IdOutOfRange(ServerId(6))), entry);
+    }
+
+    #[test]
+    fn ids_are_unchecked_without_range() {
+        //Act
+        let entry = LogEntry::<Payload>::decode(
+            &configuration_with_ids(&[1, 2, 1000]),
+            &DecodeOptions::default()
+        );
+
+        //Assert
+        assert!(entry.is_ok());
+    }
+
+    fn legacy_members_configuration() -> JsonValue {
+        json!({
+            "type": "SingleConfiguration",
+            "term": 2,
+            "command": {
+                "oldConfiguration": {
+                    "members": [1, 2],
+                },
+                "configuration": {
+                    "instanceIds": [1, 2, 3],
+                    "members": [7],
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn members_key_is_read_when_accepted() {
+        //Arrange
+        let options = DecodeOptions {
+            accept_members_key: true,
+            ..DecodeOptions::default()
+        };
+
+        //Act
+        let entry = LogEntry::<Payload>::decode(&legacy_members_configuration(), &options);
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 2,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(1, 2),
+                    configuration: servers!(1, 2, 3),
+                    learners: servers!(),
+                    change_id: None,
+                }),
+            }),
+            entry
+        );
+    }
+
+    #[test]
+    fn members_key_is_ignored_by_default() {
+        //Act
+        let entry = LogEntry::<Payload>::decode(&legacy_members_configuration(), &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(
+            Ok(LogEntry {
+                term: 2,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(),
+                    configuration: servers!(1, 2, 3),
+                    learners: servers!(),
+                    change_id: None,
+                }),
+            }),
+            entry
+        );
+    }
+
+    #[test]
+    fn overlong_line_is_skipped_and_stream_continues() {
+        //Arrange
+        let entry = json!({"type": "Payload", "term": 2, "command": {"value": 7}}).to_string();
+        let long = json!({"type": "Payload", "term": 2, "command": {"value": "x".repeat(200)}}).to_string();
+        let file = format!("{}\n{}\n\n{}\n", entry, long, entry);
+        // A small buffer makes the long line span many reads.
+        let reader = BufReader::with_capacity(8, Cursor::new(file));
+
+        //Act
+        let decoded = decode_log_stream::<_, Payload>(reader, DecodeOptions::default(), 100).collect::<Vec<_>>();
+
+        //Assert
+        let expected = LogEntry {
+            term: 2,
+            command: Some(Command::Custom(Payload(7))),
+        };
+        assert_eq!(
+            vec![
+                Ok(expected.clone()),
+                Err(LogEntryError::LineTooLong(100)),
+                Ok(expected),
+            ],
+            decoded
+        );
+    }
+
+    #[test]
+    fn lines_within_the_limit_decode() {
+        //Arrange
+        let file = "{\"term\": 1}\r\n{\"term\": 2}";
+
+        //Act
+        let terms = decode_log_stream::<_, Payload>(Cursor::new(file), DecodeOptions::default(), 12)
+            .map(|entry| entry.map(|entry| entry.term))
+            .collect::<Vec<_>>();
+
+        //Assert
+        assert_eq!(vec![Ok(1), Ok(2)], terms);
+    }
+}
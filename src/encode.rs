@@ -0,0 +1,110 @@
+use crate::{
+    log_entry::{sorted_instance_ids, Command, CustomCommand, LogEntry},
+    server_id::ServerId,
+};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default)]
+pub struct EncodeOptions {
+    // Write single configurations as the servers added to and removed from
+    // this prior configuration instead of as full sets. The old configuration
+    // is not written; decoding takes it to be the prior configuration.
+    pub delta_config: Option<HashSet<ServerId>>,
+}
+
+impl<T: CustomCommand> LogEntry<T> {
+    pub fn encode(&self, options: &EncodeOptions) -> JsonValue {
+        match (&self.command, &options.delta_config) {
+            (Some(Command::SingleConfiguration{configuration, learners, change_id, ..}), Some(prior)) => {
+                let mut command = json!({
+                    "add": sorted_instance_ids(&(configuration - prior)),
+                    "remove": sorted_instance_ids(&(prior - configuration)),
+                });
+                if !learners.is_empty() {
+                    command["learners"] = json!({
+                        "instanceIds": sorted_instance_ids(learners),
+                    });
+                }
+                if let Some(change_id) = change_id {
+                    command["changeId"] = JsonValue::from(*change_id);
+                }
+                json!({
+                    "term": self.term,
+                    "type": "SingleConfiguration",
+                    "command": command,
+                })
+            },
+            _ => self.to_json(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode::{DecodeOptions, LogEntryError},
+        testing::{servers, Payload},
+    };
+
+    #[test]
+    fn delta_config_round_trip_with_add_and_remove() {
+        //Arrange
+        let prior = servers!(1, 2, 3);
+        let entry = LogEntry::<Payload> {
+            term: 4,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: prior.clone(),
+                configuration: servers!(2, 3, 4),
+                learners: servers!(),
+                change_id: None,
+            }),
+        };
+
+        //Act
+        let encoded = entry.encode(&EncodeOptions {
+            delta_config: Some(prior.clone()),
+        });
+        let decoded = LogEntry::decode(&encoded, &DecodeOptions {
+            delta_config: Some(prior),
+            ..DecodeOptions::default()
+        });
+
+        //Assert
+        assert_eq!(
+            json!({
+                "term": 4,
+                "type": "SingleConfiguration",
+                "command": {
+                    "add": [4],
+                    "remove": [1],
+                },
+            }),
+            encoded
+        );
+        assert_eq!(Ok(entry), decoded);
+    }
+
+    #[test]
+    fn delta_config_requires_prior_configuration_to_decode() {
+        //Arrange
+        let encoded = json!({
+            "term": 4,
+            "type": "SingleConfiguration",
+            "command": {
+                "add": [4],
+                "remove": [],
+            },
+        });
+
+        //Act
+        let decoded = LogEntry::<Payload>::decode(&encoded, &DecodeOptions::default());
+
+        //Assert
+        assert_eq!(Err(LogEntryError::MissingPriorConfiguration), decoded);
+    }
+}
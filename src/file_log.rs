@@ -0,0 +1,319 @@
+// A log persisted as a file of records, each a big-endian CRC-32 of the
+// payload followed by the frame carrying it, see `framing`. A sidecar index
+// file holds each entry's byte offset as a big-endian `u64`, so reading an
+// entry is a single seek. Entries are numbered from 1. Records are written
+// and synced before their offsets, so after a crash the index never points
+// past a complete record except at a torn final write, which `open`
+// discards along with any corrupt record and everything after it. An open
+// `FileLog` holds an exclusive lock on its records file, so no other
+// `FileLog`, nor an `MmapLogReader`, can have the file open at the same time.
+
+use crate::{
+    crc32::crc32,
+    framing::{decode_payload, encode_frame, split_frame, LENGTH_LEN},
+    log::LogError,
+    log_entry::{CustomCommand, LogEntry},
+    storage::LogStorage,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+const OFFSET_LEN: usize = 8;
+const CHECKSUM_LEN: usize = 4;
+
+pub struct FileLog<T> {
+    records: File,
+    index: File,
+    offsets: Vec<u64>,
+    end: u64,
+    _command: PhantomData<T>,
+}
+
+impl<T: CustomCommand> FileLog<T> {
+    // Opens the log at `path`, creating it if needed, with its index at
+    // `path` plus `.idx`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let open = |path: &Path| OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path);
+        let records = open(path.as_ref())?;
+        records.try_lock()?;
+        let mut log = Self {
+            records,
+            index: open(&index_path(path.as_ref()))?,
+            offsets: Vec::new(),
+            end: 0,
+            _command: PhantomData,
+        };
+        log.recover()?;
+        Ok(log)
+    }
+
+    fn recover(&mut self) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        self.index.read_to_end(&mut bytes)?;
+        self.offsets = bytes
+            .chunks_exact(OFFSET_LEN)
+            .map(|offset| u64::from_be_bytes(offset.try_into().unwrap()))
+            .collect();
+        let mut records = Vec::new();
+        self.records.seek(SeekFrom::Start(0))?;
+        self.records.read_to_end(&mut records)?;
+        self.end = 0;
+        let mut valid = 0;
+        for (position, &offset) in self.offsets.iter().enumerate() {
+            if offset != self.end {
+                break;
+            }
+            match decode_record::<T>(position + 1, &records[offset as usize..]) {
+                Ok((_, length)) => self.end += length as u64,
+                Err(_) => break,
+            }
+            valid += 1;
+        }
+        self.offsets.truncate(valid);
+        // Anything past the last intact record is torn, corrupt or
+        // truncated away.
+        self.records.set_len(self.end)?;
+        self.write_index_from(0)
+    }
+
+    // Rewrites the index from entry `position` (0-based) onward.
+    fn write_index_from(&mut self, position: usize) -> io::Result<()> {
+        let bytes = self.offsets[position..]
+            .iter()
+            .flat_map(|offset| offset.to_be_bytes())
+            .collect::<Vec<_>>();
+        self.index.set_len((position * OFFSET_LEN) as u64)?;
+        self.index.seek(SeekFrom::Start((position * OFFSET_LEN) as u64))?;
+        self.index.write_all(&bytes)?;
+        self.index.sync_data()
+    }
+
+    fn read_entry(&self, index: usize) -> io::Result<LogEntry<T>> {
+        let missing = || io::Error::new(io::ErrorKind::NotFound, format!("entry {} is not in the log", index));
+        let start = *index
+            .checked_sub(1)
+            .and_then(|position| self.offsets.get(position))
+            .ok_or_else(missing)?;
+        let end = self.offsets.get(index).copied().unwrap_or(self.end);
+        let mut record = vec![0; (end - start) as usize];
+        let mut records = &self.records;
+        records.seek(SeekFrom::Start(start))?;
+        records.read_exact(&mut record)?;
+        decode_record(index, &record).map(|(entry, _)| entry)
+    }
+}
+
+fn encode_record<T: CustomCommand>(entry: &LogEntry<T>) -> io::Result<Vec<u8>> {
+    let frame = encode_frame(entry)?;
+    let checksum = crc32(&frame[LENGTH_LEN..frame.len() - LENGTH_LEN]);
+    let mut record = checksum.to_be_bytes().to_vec();
+    record.extend(frame);
+    Ok(record)
+}
+
+fn corrupt(index: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, LogError::Corrupt{index})
+}
+
+// Splits the first complete record off `bytes`, returning its payload and
+// the remaining bytes, or `None` if `bytes` ends partway through a record. A
+// malformed frame or a failed checksum is reported as `LogError::Corrupt`.
+pub(crate) fn split_record(index: usize, bytes: &[u8]) -> Option<io::Result<(&[u8], &[u8])>> {
+    if bytes.len() < CHECKSUM_LEN {
+        return None;
+    }
+    let (checksum, frame) = bytes.split_at(CHECKSUM_LEN);
+    Some(match split_frame(frame)? {
+        Ok((payload, rest)) if crc32(payload).to_be_bytes() == checksum => Ok((payload, rest)),
+        _ => Err(corrupt(index)),
+    })
+}
+
+// Splits the last record off `bytes`, returning the preceding bytes and its
+// payload, or `None` if `bytes` is empty. A torn final write is reported as
+// by `split_frame_back`, and a failed checksum as `LogError::Corrupt`.
+#[cfg(feature = "mmap")]
+pub(crate) fn split_record_back(index: usize, bytes: &[u8]) -> Option<io::Result<(&[u8], &[u8])>> {
+    Some(crate::framing::split_frame_back(bytes)?.and_then(|(rest, payload)| {
+        let checksum = rest
+            .len()
+            .checked_sub(CHECKSUM_LEN)
+            .map(|start| &rest[start..])
+            .ok_or_else(|| corrupt(index))?;
+        if crc32(payload).to_be_bytes() != checksum {
+            return Err(corrupt(index));
+        }
+        Ok((&rest[..rest.len() - CHECKSUM_LEN], payload))
+    }))
+}
+
+// Decodes the record at the start of `bytes`, returning the entry and the
+// record's length. A record that is cut short or fails its checksum is
+// reported as `LogError::Corrupt`.
+fn decode_record<T: CustomCommand>(index: usize, bytes: &[u8]) -> io::Result<(LogEntry<T>, usize)> {
+    let (payload, rest) = split_record(index, bytes).unwrap_or_else(|| Err(corrupt(index)))?;
+    Ok((decode_payload(payload)?, bytes.len() - rest.len()))
+}
+
+impl<T: CustomCommand> LogStorage<T> for FileLog<T> {
+    fn append(&mut self, entries: &[LogEntry<T>]) -> io::Result<()> {
+        let position = self.offsets.len();
+        let mut bytes = Vec::new();
+        for entry in entries {
+            self.offsets.push(self.end + bytes.len() as u64);
+            bytes.extend(encode_record(entry)?);
+        }
+        self.records.seek(SeekFrom::Start(self.end))?;
+        self.records.write_all(&bytes)?;
+        self.records.sync_data()?;
+        self.end += bytes.len() as u64;
+        self.write_index_from(position)
+    }
+
+    fn read(&self, range: Range<usize>) -> io::Result<Vec<LogEntry<T>>> {
+        range.map(|index| self.read_entry(index)).collect()
+    }
+
+    // The discarded records are cut off the file too, so that a reader
+    // walking the records file without the index never sees them.
+    fn truncate(&mut self, index: usize) -> io::Result<()> {
+        let position = index.saturating_sub(1);
+        if position >= self.offsets.len() {
+            return Ok(());
+        }
+        self.end = self.offsets[position];
+        self.offsets.truncate(position);
+        self.records.set_len(self.end)?;
+        self.write_index_from(position)
+    }
+
+    fn last_index(&self) -> usize {
+        self.offsets.len()
+    }
+}
+
+fn index_path(path: &Path) -> PathBuf {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".idx");
+    PathBuf::from(index_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::Command,
+        testing::Payload,
+    };
+    use std::fs;
+
+    fn entry(term: u64, value: usize) -> LogEntry<Payload> {
+        LogEntry {
+            term,
+            command: Some(Command::Custom(Payload(value))),
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raft-file-log-{}-{}.log", name, std::process::id()))
+    }
+
+    fn remove(path: &Path) {
+        fs::remove_file(path).unwrap();
+        fs::remove_file(index_path(path)).unwrap();
+    }
+
+    #[test]
+    fn entries_survive_reopening() {
+        //Arrange
+        let path = temp_path("reopen");
+        let mut log = FileLog::<Payload>::open(&path).unwrap();
+        log.append(&[entry(1, 10), entry(1, 11), entry(2, 12)]).unwrap();
+        log.truncate(3).unwrap();
+        log.append(&[entry(3, 13)]).unwrap();
+        drop(log);
+
+        //Act
+        let log = FileLog::<Payload>::open(&path).unwrap();
+        let entries = log.read(1..4);
+        let beyond = log.read(4..5);
+        remove(&path);
+
+        //Assert
+        assert_eq!(3, log.last_index());
+        assert_eq!(vec![entry(1, 10), entry(1, 11), entry(3, 13)], entries.unwrap());
+        assert_eq!(io::ErrorKind::NotFound, beyond.unwrap_err().kind());
+    }
+
+    #[test]
+    fn corrupt_record_fails_its_checksum() {
+        //Arrange
+        let path = temp_path("corrupt");
+        let mut log = FileLog::<Payload>::open(&path).unwrap();
+        log.append(&[entry(1, 10), entry(1, 11), entry(2, 12)]).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let offset = log.offsets[1] as usize + CHECKSUM_LEN + LENGTH_LEN + 2;
+        bytes[offset] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+
+        //Act
+        let error = log.read(2..3).unwrap_err();
+        let intact = log.read(1..2);
+        drop(log);
+        let reopened = FileLog::<Payload>::open(&path).unwrap();
+        remove(&path);
+
+        //Assert
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(
+            Some(&LogError::Corrupt{index: 2}),
+            error.get_ref().and_then(|error| error.downcast_ref::<LogError>())
+        );
+        assert_eq!(vec![entry(1, 10)], intact.unwrap());
+        assert_eq!(1, reopened.last_index());
+    }
+
+    #[test]
+    fn log_file_is_locked_while_open() {
+        //Arrange
+        let path = temp_path("locked");
+        let log = FileLog::<Payload>::open(&path).unwrap();
+
+        //Act
+        let second = FileLog::<Payload>::open(&path).map(|_| ());
+        drop(log);
+        let reopened = FileLog::<Payload>::open(&path).map(|_| ());
+        remove(&path);
+
+        //Assert
+        assert_eq!(io::ErrorKind::WouldBlock, second.unwrap_err().kind());
+        assert!(reopened.is_ok());
+    }
+
+    #[test]
+    fn torn_final_write_is_discarded() {
+        //Arrange
+        let path = temp_path("torn");
+        let mut log = FileLog::<Payload>::open(&path).unwrap();
+        log.append(&[entry(1, 10), entry(1, 11)]).unwrap();
+        drop(log);
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 1).unwrap();
+
+        //Act
+        let mut log = FileLog::<Payload>::open(&path).unwrap();
+        let recovered = log.read(1..2);
+        log.append(&[entry(2, 12)]).unwrap();
+        let after_append = log.read(1..3);
+        remove(&path);
+
+        //Assert
+        assert_eq!(vec![entry(1, 10)], recovered.unwrap());
+        assert_eq!(vec![entry(1, 10), entry(2, 12)], after_append.unwrap());
+    }
+}
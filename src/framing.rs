@@ -0,0 +1,239 @@
+// Entries travel as frames: a big-endian `u32` payload length, the entry's
+// JSON encoding, then the same length again so a file of frames can also be
+// walked backward from its end.
+
+use crate::log_entry::{CustomCommand, LogEntry};
+use serde_json::Value as JsonValue;
+use std::io;
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub(crate) const LENGTH_LEN: usize = 4;
+
+pub(crate) fn encode_frame<T: CustomCommand>(entry: &LogEntry<T>) -> io::Result<Vec<u8>> {
+    let payload = entry.to_json().to_string().into_bytes();
+    let length = encode_length(payload.len())?;
+    let mut frame = Vec::with_capacity(2 * LENGTH_LEN + payload.len());
+    frame.extend_from_slice(&length);
+    frame.extend_from_slice(&payload);
+    frame.extend_from_slice(&length);
+    Ok(frame)
+}
+
+// A payload too long for the `u32` length is `InvalidInput` rather than
+// being written with a truncated length.
+fn encode_length(length: usize) -> io::Result<[u8; LENGTH_LEN]> {
+    u32::try_from(length)
+        .map(u32::to_be_bytes)
+        .map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("payload of {} bytes does not fit in a frame", length),
+        ))
+}
+
+fn decode_length(bytes: &[u8]) -> usize {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+}
+
+// Splits the first complete frame off `bytes`, returning its payload and the
+// remaining bytes. Returns `None` if `bytes` ends partway through a frame,
+// and `InvalidData` if the frame's trailing length disagrees with its
+// leading one.
+pub(crate) fn split_frame(bytes: &[u8]) -> Option<io::Result<(&[u8], &[u8])>> {
+    if bytes.len() < LENGTH_LEN {
+        return None;
+    }
+    let (prefix, rest) = bytes.split_at(LENGTH_LEN);
+    let length = decode_length(prefix);
+    if rest.len() < length + LENGTH_LEN {
+        return None;
+    }
+    let (payload, rest) = rest.split_at(length);
+    let (suffix, rest) = rest.split_at(LENGTH_LEN);
+    if suffix != prefix {
+        return Some(Err(io::Error::new(io::ErrorKind::InvalidData, "frame length suffix does not match prefix")));
+    }
+    Some(Ok((payload, rest)))
+}
+
+// Splits the last complete frame off `bytes`, returning the preceding bytes
+// and its payload. Returns `None` if `bytes` is empty, and `InvalidData` if
+// the trailing length does not describe a well-formed frame, as happens when
+// the file ends in a torn write.
+#[cfg(feature = "mmap")]
+pub(crate) fn split_frame_back(bytes: &[u8]) -> Option<io::Result<(&[u8], &[u8])>> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let torn = || io::Error::new(io::ErrorKind::InvalidData, "truncated frame at end of log");
+    if bytes.len() < 2 * LENGTH_LEN {
+        return Some(Err(torn()));
+    }
+    let (rest, length) = bytes.split_at(bytes.len() - LENGTH_LEN);
+    let length = decode_length(length);
+    if rest.len() < length + LENGTH_LEN {
+        return Some(Err(torn()));
+    }
+    let (rest, payload) = rest.split_at(rest.len() - length);
+    let (rest, prefix) = rest.split_at(rest.len() - LENGTH_LEN);
+    if decode_length(prefix) != length {
+        return Some(Err(torn()));
+    }
+    Some(Ok((rest, payload)))
+}
+
+pub(crate) fn decode_payload<T: CustomCommand>(payload: &[u8]) -> io::Result<LogEntry<T>> {
+    let json: JsonValue = serde_json::from_slice(payload)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    LogEntry::try_from(&json).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+#[cfg(feature = "tokio")]
+pub async fn write_entry<W, T>(writer: &mut W, entry: &LogEntry<T>) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: CustomCommand,
+{
+    writer.write_all(&encode_frame(entry)?).await
+}
+
+// Returns `Ok(None)` on a clean end of stream, and `UnexpectedEof` if the
+// stream ends partway through a frame. A length prefix above
+// `max_payload_bytes` is rejected as `InvalidData` before anything is
+// allocated for the payload.
+#[cfg(feature = "tokio")]
+pub async fn read_entry<R, T>(reader: &mut R, max_payload_bytes: usize) -> io::Result<Option<LogEntry<T>>>
+where
+    R: AsyncRead + Unpin,
+    T: CustomCommand,
+{
+    let mut length = [0; LENGTH_LEN];
+    let mut filled = 0;
+    while filled < length.len() {
+        let read = reader.read(&mut length[filled..]).await?;
+        if read == 0 {
+            if filled == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        filled += read;
+    }
+    let payload_len = u32::from_be_bytes(length) as usize;
+    if payload_len > max_payload_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the {} byte limit", payload_len, max_payload_bytes),
+        ));
+    }
+    let mut payload = vec![0; payload_len];
+    reader.read_exact(&mut payload).await?;
+    let mut trailer = [0; LENGTH_LEN];
+    reader.read_exact(&mut trailer).await?;
+    if trailer != length {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame length suffix does not match prefix"));
+    }
+    decode_payload(&payload).map(Some)
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::testing::Payload;
+    use crate::log_entry::Command;
+
+    #[tokio::test]
+    async fn round_trip_over_duplex_pipe() {
+        //Arrange
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let entries = vec![
+            LogEntry {
+                term: 1,
+                command: None,
+            },
+            LogEntry {
+                term: 2,
+                command: Some(Command::Custom(Payload(42))),
+            },
+        ];
+
+        //Act
+        let writer = async {
+            for entry in &entries {
+                write_entry(&mut client, entry).await.unwrap();
+            }
+            drop(client);
+        };
+        let reader = async {
+            let mut received = Vec::new();
+            while let Some(entry) = read_entry::<_, Payload>(&mut server, 1024).await.unwrap() {
+                received.push(entry);
+            }
+            received
+        };
+        let ((), received) = tokio::join!(writer, reader);
+
+        //Assert
+        assert_eq!(entries, received);
+    }
+
+    #[tokio::test]
+    async fn partial_frame_at_eof_is_an_error() {
+        //Arrange
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let frame = encode_frame(&LogEntry::<Payload> {
+            term: 1,
+            command: None,
+        }).unwrap();
+        client.write_all(&frame[..frame.len() - 2]).await.unwrap();
+        drop(client);
+
+        //Act
+        let result = read_entry::<_, Payload>(&mut server, 1024).await;
+
+        //Assert
+        assert_eq!(io::ErrorKind::UnexpectedEof, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn mismatched_length_suffix_is_rejected() {
+        //Arrange
+        let mut frame = encode_frame(&LogEntry::<Payload> {
+            term: 1,
+            command: None,
+        }).unwrap();
+        let last = frame.len() - 1;
+        frame[last] ^= 0x01;
+
+        //Act
+        let result = split_frame(&frame);
+
+        //Assert
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap().unwrap_err().kind());
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn payload_longer_than_u32_is_rejected() {
+        //Act
+        let result = encode_length(u32::MAX as usize + 1);
+
+        //Assert
+        assert_eq!(io::ErrorKind::InvalidInput, result.unwrap_err().kind());
+    }
+
+    #[tokio::test]
+    async fn oversized_length_prefix_is_rejected() {
+        //Arrange
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&u32::MAX.to_be_bytes()).await.unwrap();
+        drop(client);
+
+        //Act
+        let result = read_entry::<_, Payload>(&mut server, 1024).await;
+
+        //Assert
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+    }
+}
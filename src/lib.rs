@@ -1 +1,70 @@
-mod log_entry;
\ No newline at end of file
+mod append_entries;
+mod base64;
+mod binary;
+mod cached_command;
+mod combined;
+mod config_watcher;
+mod configuration;
+mod crc32;
+mod decode;
+mod encode;
+mod file_log;
+mod framing;
+mod log;
+mod log_entry;
+mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap_reader;
+mod node_snapshot;
+mod persistent_state;
+mod quorum;
+mod replication;
+mod request_vote;
+mod server_id;
+mod skeleton;
+mod snapshot;
+mod state_machine;
+mod storage;
+#[cfg(test)]
+mod testing;
+
+pub use append_entries::{AppendEntriesRequest, AppendEntriesResponse, WireVersion, WireVersionError};
+pub use cached_command::CachedCommand;
+pub use combined::{read_combined_stream, CombinedStream, Record};
+pub use config_watcher::{ConfigChanged, ConfigWatcher};
+pub use configuration::Configuration;
+pub use decode::{decode_log_stream, DecodeLogStream, DecodeOptions, LogEntryError};
+pub use encode::EncodeOptions;
+pub use file_log::FileLog;
+#[cfg(feature = "tokio")]
+pub use framing::{read_entry, write_entry};
+pub use log::{Applied, AppendResult, ApplyError, Log, LogError, ReconcilePlan};
+pub use log_entry::{
+    Command,
+    CommandDecodeError,
+    ConfigError,
+    CustomCommand,
+    LogEntry,
+    LogEntryBuilder,
+    LogEntryFactory,
+    SingleConfigView,
+};
+pub use metrics::{LogMetrics, SizeStats};
+#[cfg(feature = "mmap")]
+pub use mmap_reader::{MmapEntries, MmapEntriesRev, MmapLogReader};
+pub use node_snapshot::NodeSnapshot;
+pub use persistent_state::PersistentState;
+pub use quorum::{majority, quorums_overlap};
+pub use replication::{plan_replication, ReplicationAction, ReplicationProgress};
+pub use request_vote::{
+    compare_up_to_date,
+    handle_request_vote,
+    RequestVoteRequest,
+    RequestVoteResponse,
+    VoteDenialReason,
+};
+pub use server_id::ServerId;
+pub use skeleton::LogEntrySkeleton;
+pub use snapshot::{SnapshotChunk, SnapshotChunker, SnapshotMeta, SnapshotPolicy};
+pub use state_machine::{maintain, verify_determinism, Snapshottable, StateMachine};
+pub use storage::LogStorage;
@@ -0,0 +1,1527 @@
+use crate::{
+    decode::{DecodeOptions, LogEntryError},
+    log_entry::{sorted_instance_ids, Command, CustomCommand, LogEntry},
+    metrics::SizeStats,
+    server_id::ServerId,
+    snapshot::SnapshotMeta,
+    state_machine::StateMachine,
+};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Debug,
+    ops::Range,
+};
+use thiserror::Error;
+
+// Entries are addressed by their absolute, 1-based Raft index. Everything at
+// or below `base_index` has been compacted into a snapshot whose last entry
+// had term `base_term` and whose committed voters were `base_configuration`,
+// if known. `last_applied` is the index of the last entry handed
+// to the state machine. `reserved` holds the indices of placeholders
+// appended by `reserve` that have not been filled yet. `client_index` is
+// only kept once `enable_client_index` has been called.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Log<T> {
+    entries: Vec<LogEntry<T>>,
+    base_index: usize,
+    base_term: u64,
+    base_configuration: Option<HashSet<ServerId>>,
+    last_applied: usize,
+    reserved: BTreeSet<usize>,
+    client_index: Option<ClientIndex<T>>,
+}
+
+// The highest index of each client's custom commands, including those
+// inside batches. Indices of compacted entries are kept, but a client whose
+// later commands are truncated away and whose earlier ones were compacted
+// is forgotten.
+struct ClientIndex<T> {
+    client_id: fn(&T) -> Option<usize>,
+    latest: HashMap<usize, usize>,
+}
+
+impl<T> ClientIndex<T> {
+    fn record(&mut self, index: usize, entry: &LogEntry<T>) {
+        let custom_commands = match &entry.command {
+            Some(Command::Custom(custom_command)) => vec![custom_command],
+            Some(Command::Batch(commands)) => commands
+                .iter()
+                .filter_map(|command| match command {
+                    Command::Custom(custom_command) => Some(custom_command),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        for custom_command in custom_commands {
+            if let Some(client_id) = (self.client_id)(custom_command) {
+                self.latest.insert(client_id, index);
+            }
+        }
+    }
+
+    fn record_all(&mut self, base_index: usize, entries: &[LogEntry<T>]) {
+        for (offset, entry) in entries.iter().enumerate() {
+            self.record(base_index + offset + 1, entry);
+        }
+    }
+}
+
+impl<T> Debug for ClientIndex<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.latest.fmt(f)
+    }
+}
+
+impl<T> PartialEq for ClientIndex<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.latest == other.latest
+    }
+}
+
+impl<T> Eq for ClientIndex<T> {}
+
+// The steps that make one log match another: drop everything from
+// `truncate_from` onwards (if set), then append `entries`.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ReconcilePlan<'a, T> {
+    pub truncate_from: Option<usize>,
+    pub entries: &'a [LogEntry<T>],
+}
+
+// The follower's answer to AppendEntries. On rejection, `conflict_term` is
+// the term of the follower's entry at `prev_index` and `conflict_index` the
+// first index holding that term, so the leader can skip the whole term; if
+// the follower's log is too short, `conflict_term` is `None` and
+// `conflict_index` is one past its last entry. `AppliedConflict` means the
+// request contradicts an entry this node has already applied, which only a
+// faulty leader can send; the log is left unchanged.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AppendResult {
+    Accepted,
+    Rejected{
+        conflict_index: usize,
+        conflict_term: Option<u64>,
+    },
+    AppliedConflict(usize),
+}
+
+// The outcome of `apply_committed`: the state machine's response to each
+// entry applied, by index, and whether the commit index given was beyond the
+// end of the log and had to be clamped.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Applied<R> {
+    pub responses: Vec<(usize, R)>,
+    pub commit_index_clamped: bool,
+}
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum ApplyError {
+    #[error("entry {to} is beyond the commit index {commit_index}")]
+    BeyondCommit{
+        to: usize,
+        commit_index: usize,
+    },
+    #[error("entry {from} has been compacted into a snapshot ending at {base_index}")]
+    BelowBase{
+        from: usize,
+        base_index: usize,
+    },
+    #[error("entry {0} is missing from the log")]
+    Gap(usize),
+}
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum LogError {
+    #[error("entry {index} is already committed (commit index {commit_index})")]
+    Committed{
+        index: usize,
+        commit_index: usize,
+    },
+    #[error("entry {0} is not a reserved placeholder")]
+    NotReserved(usize),
+    #[error("entry {index} is not the tail-most reserved placeholder {tail}")]
+    NotTailReserved{
+        index: usize,
+        tail: usize,
+    },
+    #[error("no configuration change is in the log")]
+    NoPendingChange,
+    #[error("entry {index} failed its checksum")]
+    Corrupt{
+        index: usize,
+    },
+}
+
+impl<T> Log<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            base_index: 0,
+            base_term: 0,
+            base_configuration: None,
+            last_applied: 0,
+            reserved: BTreeSet::new(),
+            client_index: None,
+        }
+    }
+
+    pub fn base_index(&self) -> usize {
+        self.base_index
+    }
+
+    // The lowest index `get` can still return, whether or not the log holds
+    // an entry there yet.
+    pub fn first_index(&self) -> usize {
+        self.base_index + 1
+    }
+
+    pub fn base_term(&self) -> u64 {
+        self.base_term
+    }
+
+    pub fn last_applied(&self) -> usize {
+        self.last_applied
+    }
+
+    // Once every entry has been compacted the last index and term are those
+    // of the snapshot.
+    pub fn last_index(&self) -> usize {
+        self.base_index + self.entries.len()
+    }
+
+    pub fn last_term(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(self.base_term, |entry| entry.term)
+    }
+
+    // The index of the latest command from `client_id`, if the client index
+    // is enabled and the client has one.
+    pub fn latest_client_index(&self, client_id: usize) -> Option<usize> {
+        self.client_index
+            .as_ref()
+            .and_then(|client_index| client_index.latest.get(&client_id))
+            .copied()
+    }
+
+    // Terms never decrease along a log, so they can be binary searched. The
+    // term of the last compacted entry counts as present.
+    pub fn contains_term(&self, term: u64) -> bool {
+        (self.base_index > 0 && self.base_term == term)
+            || self
+                .entries
+                .binary_search_by(|entry| entry.term.cmp(&term))
+                .is_ok()
+    }
+
+    pub fn append(&mut self, entry: LogEntry<T>) -> usize {
+        let index = self.base_index + self.entries.len() + 1;
+        if let Some(client_index) = &mut self.client_index {
+            client_index.record(index, &entry);
+        }
+        self.entries.push(entry);
+        index
+    }
+
+    // Appends a no-op placeholder to hold a place in the log order until its
+    // command is ready, and returns its index.
+    pub fn reserve(&mut self, term: u64) -> usize {
+        let index = self.append(LogEntry {
+            term,
+            command: None,
+        });
+        self.reserved.insert(index);
+        index
+    }
+
+    // Replaces the placeholder at `index` with `command`. The slot must
+    // be the tail-most one still reserved, that is neither filled nor
+    // truncated away, and must not be committed, since committed entries
+    // never change.
+    pub fn fill(&mut self, index: usize, command: Command<T>, commit_index: usize) -> Result<(), LogError> {
+        if index <= commit_index {
+            return Err(LogError::Committed{index, commit_index});
+        }
+        match self.reserved.last() {
+            None => return Err(LogError::NotReserved(index)),
+            Some(&tail) if tail != index => {
+                return Err(if self.reserved.contains(&index) {
+                    LogError::NotTailReserved{index, tail}
+                } else {
+                    LogError::NotReserved(index)
+                });
+            }
+            Some(_) => {}
+        }
+        self.reserved.remove(&index);
+        let offset = index - self.base_index - 1;
+        self.entries[offset].command = Some(command);
+        if let Some(client_index) = &mut self.client_index {
+            client_index.record(index, &self.entries[offset]);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&LogEntry<T>> {
+        index
+            .checked_sub(self.base_index + 1)
+            .and_then(|offset| self.entries.get(offset))
+    }
+
+    // The term of the entry before `index`, sent as `prevLogTerm` with
+    // entries starting at `index`. Just above the snapshot base this is the
+    // snapshot's last term, which is 0 for a log that was never compacted.
+    pub fn prev_term(&self, index: usize) -> Option<u64> {
+        let prev_index = index.checked_sub(1)?;
+        if prev_index == self.base_index {
+            return Some(self.base_term);
+        }
+        self.get(prev_index).map(|entry| entry.term)
+    }
+
+    // The entries a follower whose log matches ours up to `follower_match`
+    // still needs, or `None` if some of them are compacted and it must be
+    // sent the snapshot instead.
+    pub fn catchup_range(&self, follower_match: usize) -> Option<Range<usize>> {
+        if follower_match < self.base_index {
+            return None;
+        }
+        Some(follower_match.min(self.last_index()) + 1..self.last_index() + 1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &LogEntry<T>)> {
+        self.iter_from(self.base_index + 1)
+    }
+
+    fn iter_from(&self, index: usize) -> impl Iterator<Item = (usize, &LogEntry<T>)> {
+        let skipped = index
+            .saturating_sub(self.base_index + 1)
+            .min(self.entries.len());
+        let first_index = self.base_index + skipped + 1;
+        self.entries[skipped..]
+            .iter()
+            .enumerate()
+            .map(move |(offset, entry)| (first_index + offset, entry))
+    }
+
+    pub fn iter_committed(&self, commit_index: usize) -> impl Iterator<Item = (usize, &LogEntry<T>)> {
+        self.iter().take_while(move |(index, _)| *index <= commit_index)
+    }
+
+    pub fn pending_entries(&self, commit_index: usize) -> impl Iterator<Item = (usize, &LogEntry<T>)> {
+        self.iter_from(commit_index + 1)
+    }
+
+    // Indices of configuration entries whose voters are the same as those of
+    // the configuration entry before them, i.e. changes that changed nothing.
+    pub fn find_redundant_configs(&self) -> Vec<usize> {
+        let mut previous_voters = None;
+        let mut redundant = Vec::new();
+        for (index, entry) in self.iter() {
+            let voters = entry.command.as_ref().and_then(Command::active_voters);
+            if let Some(voters) = voters {
+                if previous_voters.as_ref() == Some(&voters) {
+                    redundant.push(index);
+                }
+                previous_voters = Some(voters);
+            }
+        }
+        redundant
+    }
+
+    // The voters of the latest configuration entry at or below both
+    // `snapshot_index` and `commit_index`, for recording in the snapshot's
+    // metadata. Once that entry has been compacted away, the voters recorded
+    // for the snapshot base are used instead.
+    pub fn committed_configuration(&self, snapshot_index: usize, commit_index: usize) -> Option<HashSet<ServerId>> {
+        self.iter_committed(snapshot_index.min(commit_index))
+            .filter_map(|(_, entry)| entry.command.as_ref().and_then(Command::active_voters))
+            .last()
+            .or_else(|| self.base_configuration.clone())
+    }
+
+    // Whether the configuration entry tagged with `change_id` is in the log
+    // at or below `commit_index`.
+    pub fn is_change_committed(&self, change_id: u64, commit_index: usize) -> bool {
+        self.iter_committed(commit_index).any(|(_, entry)| {
+            entry
+                .command
+                .as_ref()
+                .and_then(Command::change_id)
+                == Some(change_id)
+        })
+    }
+
+    // Appends a configuration reverting to `prior`, which aborts the latest
+    // configuration change. Once that change is committed it can no longer
+    // be abandoned, only undone by a change of its own.
+    pub fn append_revert(&mut self, term: u64, prior: &HashSet<ServerId>, commit_index: usize) -> Result<usize, LogError> {
+        let (index, voters) = self
+            .iter()
+            .filter_map(|(index, entry)| {
+                entry
+                    .command
+                    .as_ref()
+                    .and_then(Command::active_voters)
+                    .map(|voters| (index, voters))
+            })
+            .last()
+            .ok_or(LogError::NoPendingChange)?;
+        if index <= commit_index {
+            return Err(LogError::Committed{index, commit_index});
+        }
+        Ok(self.append(LogEntry {
+            term,
+            command: Some(Command::revert_to(prior).with_old_configuration(voters)),
+        }))
+    }
+
+    // Discards entries up to and including `index`, which must already be
+    // captured by a snapshot.
+    pub fn compact(&mut self, index: usize) {
+        let index = index.min(self.last_index());
+        if index <= self.base_index {
+            return;
+        }
+        if let Some(entry) = self.get(index) {
+            self.base_term = entry.term;
+        }
+        self.base_configuration = self.committed_configuration(index, index);
+        self.entries.drain(..index - self.base_index);
+        self.reserved.retain(|&reserved| reserved > index);
+        self.base_index = index;
+        self.last_applied = self.last_applied.max(index);
+    }
+
+    // Rolls the log back as if the node crashed and recovered: entries above
+    // `index` are dropped and `last_applied` is reset to `applied_watermark`.
+    pub fn rewind_to(&mut self, index: usize, applied_watermark: usize) {
+        assert!(
+            applied_watermark <= index,
+            "applied watermark {} is beyond rewind index {}",
+            applied_watermark,
+            index
+        );
+        assert!(
+            index >= self.base_index,
+            "cannot rewind to {}, below the snapshot base {}",
+            index,
+            self.base_index
+        );
+        // The snapshot already holds everything up to the base, so the state
+        // machine can never be behind it.
+        assert!(
+            applied_watermark >= self.base_index,
+            "applied watermark {} is below the snapshot base {}",
+            applied_watermark,
+            self.base_index
+        );
+        self.drop_after(index);
+        self.last_applied = applied_watermark;
+    }
+
+    // Drops the entries at `index` and above, as a follower does when they
+    // conflict with the leader's. Applied entries are committed and can
+    // never conflict, so truncating them is a bug.
+    pub fn truncate_from(&mut self, index: usize) {
+        assert!(
+            index > self.base_index,
+            "cannot truncate from {}, within the snapshot ending at {}",
+            index,
+            self.base_index
+        );
+        assert!(
+            index > self.last_applied,
+            "cannot truncate from {}, entries up to {} are applied",
+            index,
+            self.last_applied
+        );
+        self.drop_after(index - 1);
+    }
+
+    fn drop_after(&mut self, index: usize) {
+        self.entries.truncate(index - self.base_index);
+        self.reserved.retain(|&reserved| reserved <= index);
+        if let Some(client_index) = &mut self.client_index {
+            client_index.latest.retain(|_, latest| *latest <= index);
+            client_index.record_all(self.base_index, &self.entries);
+        }
+    }
+
+    // Applies an AppendEntries request whose `entries` follow `prev_index`.
+    // Entries already present with the same term are kept, so a resent
+    // request changes nothing; the first entry whose term differs is
+    // truncated along with everything after it. Entries inside the snapshot
+    // are committed and assumed to match.
+    pub fn append_entries(&mut self, prev_index: usize, prev_term: u64, entries: Vec<LogEntry<T>>) -> AppendResult {
+        if prev_index > self.last_index() {
+            return AppendResult::Rejected{
+                conflict_index: self.last_index() + 1,
+                conflict_term: None,
+            };
+        }
+        if prev_index >= self.base_index {
+            let term = self.prev_term(prev_index + 1);
+            if term != Some(prev_term) {
+                let mut conflict_index = prev_index;
+                while conflict_index > self.base_index + 1 && self.prev_term(conflict_index) == term {
+                    conflict_index -= 1;
+                }
+                return AppendResult::Rejected{conflict_index, conflict_term: term};
+            }
+        }
+        for (index, entry) in (prev_index + 1..).zip(entries) {
+            if index <= self.base_index {
+                continue;
+            }
+            match self.get(index) {
+                Some(existing) if existing.term == entry.term => continue,
+                Some(_) if index <= self.last_applied => return AppendResult::AppliedConflict(index),
+                Some(_) => self.truncate_from(index),
+                None => {},
+            }
+            self.append(entry);
+        }
+        AppendResult::Accepted
+    }
+
+    pub fn replace_all(&mut self, meta: &SnapshotMeta, entries: Vec<LogEntry<T>>) {
+        self.base_index = meta.last_included_index;
+        self.base_term = meta.last_included_term;
+        self.base_configuration = Some(meta.configuration.clone()).filter(|configuration| !configuration.is_empty());
+        self.last_applied = meta.last_included_index;
+        self.entries = entries;
+        self.reserved.clear();
+        if let Some(client_index) = &mut self.client_index {
+            client_index.latest.clear();
+            client_index.record_all(self.base_index, &self.entries);
+        }
+    }
+
+    // Entries compacted away in either log are assumed to match, since only
+    // committed entries are ever compacted.
+    pub fn reconcile_plan<'a>(&self, target: &'a Log<T>) -> ReconcilePlan<'a, T> {
+        let last_common_index = self.last_index().min(target.last_index());
+        let mut index = self.base_index.max(target.base_index) + 1;
+        while index <= last_common_index
+            && self.get(index).map(|entry| entry.term) == target.get(index).map(|entry| entry.term)
+        {
+            index += 1;
+        }
+        ReconcilePlan {
+            truncate_from: if index <= self.last_index() {
+                Some(index)
+            } else {
+                None
+            },
+            entries: target
+                .entries
+                .get(index - target.base_index - 1..)
+                .unwrap_or(&[]),
+        }
+    }
+
+    // Checks that entries `from..=to` may be handed to the state machine:
+    // they are all committed and still present in the log.
+    pub fn assert_applicable(&self, from: usize, to: usize, commit_index: usize) -> Result<(), ApplyError> {
+        if to > commit_index {
+            return Err(ApplyError::BeyondCommit{to, commit_index});
+        }
+        if from <= self.base_index {
+            return Err(ApplyError::BelowBase{from, base_index: self.base_index});
+        }
+        if to > self.last_index() {
+            return Err(ApplyError::Gap(self.last_index().max(from - 1) + 1));
+        }
+        Ok(())
+    }
+
+    // Applies the entries up to `commit_index`, clamped to the end of the log
+    // as by `clamp_commit_index`.
+    pub fn apply_committed<SM: StateMachine<T>>(
+        &mut self,
+        state_machine: &mut SM,
+        commit_index: usize,
+    ) -> Applied<SM::Response> {
+        self.apply_committed_with_progress(state_machine, commit_index, 0, |_, _| ())
+    }
+
+    // A commit index beyond the end of the log can only come from a bug or
+    // a malformed message. It is clamped to the last index rather than
+    // trusted, and the flag reports whether it was, so the caller can warn
+    // through its own logging.
+    pub fn clamp_commit_index(&self, commit_index: usize) -> (usize, bool) {
+        let last_index = self.last_index();
+        (commit_index.min(last_index), commit_index > last_index)
+    }
+
+    // Like `apply_committed`, but calls `progress(applied, total)` after
+    // every `interval` entries so a long replay can report how far along it
+    // is. An `interval` of 0 never reports.
+    pub fn apply_committed_with_progress<SM: StateMachine<T>>(
+        &mut self,
+        state_machine: &mut SM,
+        commit_index: usize,
+        interval: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Applied<SM::Response> {
+        let (commit_index, commit_index_clamped) = self.clamp_commit_index(commit_index);
+        let total = commit_index.saturating_sub(self.last_applied);
+        let mut responses = Vec::new();
+        while self.last_applied < commit_index {
+            let index = self.last_applied + 1;
+            match self.get(index) {
+                Some(entry) => responses.push((index, state_machine.apply(index, entry))),
+                None => break,
+            }
+            self.last_applied = index;
+            if interval != 0 && responses.len() % interval == 0 {
+                progress(responses.len(), total);
+            }
+        }
+        Applied {
+            responses,
+            commit_index_clamped,
+        }
+    }
+}
+
+// The persisted form of a log: the snapshot base and the entries after it.
+// `last_applied` is volatile and restarts at the snapshot base, and
+// unfilled reservations are not persisted.
+impl<T: CustomCommand> Log<T> {
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = json!({
+            "lastIncludedIndex": self.base_index,
+            "lastIncludedTerm": self.base_term,
+            "entries": self.entries.iter().map(LogEntry::to_json).collect::<Vec<_>>(),
+        });
+        if let Some(configuration) = &self.base_configuration {
+            json["lastIncludedConfiguration"] = json!(sorted_instance_ids(configuration));
+        }
+        json
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Self, LogEntryError> {
+        let field = |field: &'static str| {
+            json.get(field)
+                .and_then(JsonValue::as_u64)
+                .ok_or(LogEntryError::MissingField(field))
+        };
+        let base_index = field("lastIncludedIndex")? as usize;
+        let base_term = field("lastIncludedTerm")?;
+        let base_configuration = match json.get("lastIncludedConfiguration") {
+            Some(configuration) => Some(
+                serde_json::from_value(configuration.clone())
+                    .map_err(|_| LogEntryError::MalformedConfiguration{field: "lastIncludedConfiguration"})?,
+            ),
+            None => None,
+        };
+        let entries = json
+            .get("entries")
+            .and_then(JsonValue::as_array)
+            .ok_or(LogEntryError::MissingField("entries"))?
+            .iter()
+            .map(|entry| LogEntry::decode(entry, &DecodeOptions::default()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            entries,
+            base_index,
+            base_term,
+            base_configuration,
+            last_applied: base_index,
+            reserved: BTreeSet::new(),
+            client_index: None,
+        })
+    }
+}
+
+impl<T: CustomCommand> Log<T> {
+    pub fn size_stats(&self) -> SizeStats {
+        SizeStats::from_sizes(self.entries.iter().map(LogEntry::size_bytes).collect())
+    }
+
+    // Starts tracking the latest index of each client's commands, as
+    // reported by `CustomCommand::client_id`, beginning with the entries
+    // already in the log.
+    pub fn enable_client_index(&mut self) {
+        let mut client_index = ClientIndex {
+            client_id: T::client_id,
+            latest: HashMap::new(),
+        };
+        client_index.record_all(self.base_index, &self.entries);
+        self.client_index = Some(client_index);
+    }
+}
+
+impl<T> Default for Log<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::CommandDecodeError,
+        testing::{servers, Payload},
+    };
+
+    fn entry(term: u64) -> LogEntry<Payload> {
+        LogEntry {
+            term,
+            command: None,
+        }
+    }
+
+    fn populated_log() -> Log<Payload> {
+        let mut log = Log::new();
+        for term in [1, 1, 2, 2, 3] {
+            log.append(entry(term));
+        }
+        log
+    }
+
+    #[test]
+    fn replace_all_with_empty_log_at_snapshot_base() {
+        //Arrange
+        let mut log = populated_log();
+        let meta = SnapshotMeta {
+            last_included_index: 10,
+            last_included_term: 4,
+            configuration: servers!(1, 2, 3),
+        };
+
+        //Act
+        log.replace_all(&meta, Vec::new());
+
+        //Assert
+        assert_eq!(10, log.base_index());
+        assert_eq!(4, log.base_term());
+        assert!(log.get(1).is_none());
+        assert!(log.get(10).is_none());
+        assert!(log.get(11).is_none());
+        assert_eq!(11, log.append(entry(5)));
+    }
+
+    #[test]
+    fn replace_all_installs_entries_after_snapshot_base() {
+        //Arrange
+        let mut log = populated_log();
+        let meta = SnapshotMeta {
+            last_included_index: 3,
+            last_included_term: 2,
+            configuration: servers!(1, 2, 3),
+        };
+
+        //Act
+        log.replace_all(&meta, vec![entry(6), entry(7)]);
+
+        //Assert
+        assert!(log.get(3).is_none());
+        assert_eq!(Some(&entry(6)), log.get(4));
+        assert_eq!(Some(&entry(7)), log.get(5));
+        assert!(log.get(6).is_none());
+    }
+
+    #[test]
+    fn apply_committed_stops_at_commit_index() {
+        //Arrange
+        struct Terms(Vec<u64>);
+        impl StateMachine<Payload> for Terms {
+            type Response = ();
+
+            fn apply(&mut self, _index: usize, entry: &LogEntry<Payload>) {
+                self.0.push(entry.term);
+            }
+        }
+        let mut log = populated_log();
+        let mut state_machine = Terms(Vec::new());
+
+        //Act
+        log.apply_committed(&mut state_machine, 3);
+        log.apply_committed(&mut state_machine, 2);
+
+        //Assert
+        assert_eq!(vec![1, 1, 2], state_machine.0);
+        assert_eq!(3, log.last_applied());
+    }
+
+    #[test]
+    fn apply_committed_clamps_commit_index_to_log_end() {
+        //Arrange
+        struct Indices(Vec<usize>);
+        impl StateMachine<Payload> for Indices {
+            type Response = ();
+
+            fn apply(&mut self, index: usize, _entry: &LogEntry<Payload>) {
+                self.0.push(index);
+            }
+        }
+        let mut log = populated_log();
+        let mut state_machine = Indices(Vec::new());
+
+        //Act
+        let beyond = log.apply_committed(&mut state_machine, 9);
+        let within = log.apply_committed(&mut state_machine, 5);
+
+        //Assert
+        assert!(beyond.commit_index_clamped);
+        assert!(!within.commit_index_clamped);
+        assert_eq!(vec![1, 2, 3, 4, 5], state_machine.0);
+        assert_eq!(5, log.last_applied());
+        assert_eq!((5, true), log.clamp_commit_index(9));
+        assert_eq!((4, false), log.clamp_commit_index(4));
+    }
+
+    #[test]
+    fn prev_term_reads_the_preceding_entry() {
+        //Arrange
+        let mut log = populated_log();
+
+        //Assert
+        assert_eq!(Some(2), log.prev_term(4));
+        assert_eq!(Some(3), log.prev_term(6));
+        assert_eq!(None, log.prev_term(7));
+
+        //Act
+        log.compact(3);
+
+        //Assert
+        assert_eq!(Some(2), log.prev_term(4));
+        assert_eq!(None, log.prev_term(3));
+    }
+
+    #[test]
+    fn prev_term_of_first_index_in_empty_log_is_zero() {
+        assert_eq!(Some(0), Log::<Payload>::new().prev_term(1));
+        assert_eq!(None, Log::<Payload>::new().prev_term(0));
+        assert_eq!(None, Log::<Payload>::new().prev_term(2));
+    }
+
+    #[test]
+    fn catchup_range_needs_uncompacted_entries() {
+        //Arrange
+        let mut log = populated_log();
+        log.compact(2);
+
+        //Assert
+        assert_eq!(None, log.catchup_range(1));
+        assert_eq!(Some(3..6), log.catchup_range(2));
+        assert_eq!(Some(6..6), log.catchup_range(5));
+        assert_eq!(Some(6..6), log.catchup_range(9));
+    }
+
+    #[test]
+    fn first_index_follows_compaction() {
+        //Arrange
+        let mut log = populated_log();
+        let uncompacted = log.first_index();
+
+        //Act
+        log.compact(3);
+
+        //Assert
+        assert_eq!(1, uncompacted);
+        assert_eq!(4, log.first_index());
+        assert!(log.get(log.first_index() - 1).is_none());
+        assert_eq!(Some(&entry(2)), log.get(log.first_index()));
+    }
+
+    #[test]
+    fn truncate_from_drops_index_and_above() {
+        //Arrange
+        let mut log = populated_log();
+        log.compact(1);
+
+        //Act
+        log.truncate_from(4);
+
+        //Assert
+        assert_eq!(3, log.last_index());
+        assert_eq!(2, log.last_term());
+        assert_eq!(Some(&entry(1)), log.get(2));
+        assert_eq!(None, log.get(4));
+        assert_eq!(4, log.append(entry(4)));
+        assert_eq!(Some(&entry(4)), log.get(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "within the snapshot")]
+    fn truncate_from_rejects_compacted_index() {
+        let mut log = populated_log();
+        log.compact(2);
+        log.truncate_from(2);
+    }
+
+    #[test]
+    fn append_revert_restores_prior_voters() {
+        //Arrange
+        let mut log = populated_log();
+        let prior = servers!(1, 2, 3);
+        log.append(LogEntry {
+            term: 3,
+            command: Some(Command::single_server_change(&prior, Some(ServerId(4)), None).unwrap()),
+        });
+
+        //Act
+        let index = log.append_revert(3, &prior, 5);
+
+        //Assert
+        assert_eq!(Ok(7), index);
+        assert_eq!(
+            Some(&Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3, 4),
+                configuration: servers!(1, 2, 3),
+                learners: servers!(),
+                change_id: None,
+            }),
+            log.get(7).and_then(|entry| entry.command.as_ref())
+        );
+        assert_eq!(Some(prior), log.committed_configuration(7, 7));
+    }
+
+    #[test]
+    fn append_revert_requires_an_uncommitted_change() {
+        //Arrange
+        let mut log = populated_log();
+        let prior = servers!(1, 2, 3);
+
+        //Act
+        let without_change = log.append_revert(3, &prior, 5);
+        log.append(LogEntry {
+            term: 3,
+            command: Some(Command::single_server_change(&prior, Some(ServerId(4)), None).unwrap()),
+        });
+        let after_commit = log.append_revert(3, &prior, 6);
+
+        //Assert
+        assert_eq!(Err(LogError::NoPendingChange), without_change);
+        assert_eq!(
+            Err(LogError::Committed{
+                index: 6,
+                commit_index: 6,
+            }),
+            after_commit
+        );
+        assert_eq!(6, log.last_index());
+    }
+
+    #[test]
+    fn append_entries_resent_is_idempotent() {
+        //Arrange
+        let mut log = populated_log();
+        let resent = || vec![entry(2), entry(2), entry(3)];
+
+        //Act
+        let first = log.append_entries(2, 1, resent());
+        let second = log.append_entries(2, 1, resent());
+
+        //Assert
+        assert_eq!(AppendResult::Accepted, first);
+        assert_eq!(AppendResult::Accepted, second);
+        assert_eq!(populated_log(), log);
+    }
+
+    #[test]
+    fn append_entries_truncates_from_conflicting_term() {
+        //Arrange
+        let mut log = populated_log();
+
+        //Act
+        let result = log.append_entries(2, 1, vec![entry(2), entry(4)]);
+
+        //Assert
+        assert_eq!(AppendResult::Accepted, result);
+        assert_eq!(log_with_terms(&[1, 1, 2, 4]), log);
+    }
+
+    #[test]
+    fn append_entries_refuses_to_truncate_applied_entries() {
+        //Arrange
+        let mut log = populated_log();
+        log.rewind_to(5, 3);
+
+        //Act
+        let result = log.append_entries(1, 1, vec![entry(1), entry(4)]);
+
+        //Assert
+        assert_eq!(AppendResult::AppliedConflict(3), result);
+        assert_eq!(Some(&entry(2)), log.get(3));
+        assert_eq!(5, log.last_index());
+        assert_eq!(3, log.last_applied());
+    }
+
+    #[test]
+    fn append_entries_rejects_mismatched_prev_entry() {
+        //Arrange
+        let mut log = populated_log();
+
+        //Act
+        let mismatched = log.append_entries(4, 3, vec![entry(3)]);
+        let too_short = log.append_entries(7, 3, vec![entry(3)]);
+
+        //Assert
+        assert_eq!(
+            AppendResult::Rejected{
+                conflict_index: 3,
+                conflict_term: Some(2),
+            },
+            mismatched
+        );
+        assert_eq!(
+            AppendResult::Rejected{
+                conflict_index: 6,
+                conflict_term: None,
+            },
+            too_short
+        );
+        assert_eq!(populated_log(), log);
+    }
+
+    fn log_with_terms(terms: &[u64]) -> Log<Payload> {
+        let mut log = Log::new();
+        for &term in terms {
+            log.append(entry(term));
+        }
+        log
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct ClientRequest(usize);
+
+    impl CustomCommand for ClientRequest {
+        fn command_type(&self) -> &'static str {
+            "ClientRequest"
+        }
+
+        fn to_json(&self) -> JsonValue {
+            json!({
+                "clientId": self.0,
+            })
+        }
+
+        fn from_json(_json: &JsonValue) -> Result<Self, CommandDecodeError> {
+            Err(CommandDecodeError::MissingType)
+        }
+
+        fn client_id(&self) -> Option<usize> {
+            Some(self.0)
+        }
+    }
+
+    fn request(term: u64, client_id: usize) -> LogEntry<ClientRequest> {
+        LogEntry {
+            term,
+            command: Some(Command::Custom(ClientRequest(client_id))),
+        }
+    }
+
+    #[test]
+    fn client_index_tracks_latest_command_per_client() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(request(1, 7));
+        log.enable_client_index();
+
+        //Act
+        for (term, client_id) in [(1, 8), (2, 7), (2, 9), (2, 8)] {
+            log.append(request(term, client_id));
+        }
+
+        //Assert
+        assert_eq!(Some(3), log.latest_client_index(7));
+        assert_eq!(Some(5), log.latest_client_index(8));
+        assert_eq!(Some(4), log.latest_client_index(9));
+        assert_eq!(None, log.latest_client_index(10));
+    }
+
+    #[test]
+    fn client_index_falls_back_after_truncation() {
+        //Arrange
+        let mut log = Log::new();
+        log.enable_client_index();
+        for (term, client_id) in [(1, 7), (1, 8), (2, 7), (2, 9)] {
+            log.append(request(term, client_id));
+        }
+
+        //Act
+        log.rewind_to(2, 0);
+
+        //Assert
+        assert_eq!(Some(1), log.latest_client_index(7));
+        assert_eq!(Some(2), log.latest_client_index(8));
+        assert_eq!(None, log.latest_client_index(9));
+        assert_eq!(None, Log::<ClientRequest>::new().latest_client_index(7));
+    }
+
+    #[test]
+    fn size_stats_summarise_entry_sizes() {
+        //Arrange
+        // Entries without a command encode as `{"term":N}`, so their sizes
+        // grow with the number of digits in the term.
+        let log = log_with_terms(&[1, 10, 10, 100, 1000]);
+
+        //Act
+        let stats = log.size_stats();
+
+        //Assert
+        assert_eq!(
+            SizeStats {
+                total_bytes: 57,
+                min: 10,
+                max: 13,
+                mean: 11.4,
+                p50: 11,
+                p99: 13,
+            },
+            stats
+        );
+        assert_eq!(SizeStats::default(), Log::<Payload>::new().size_stats());
+    }
+
+    #[test]
+    fn reconcile_plan_truncates_conflicting_suffix_then_appends() {
+        //Arrange
+        let follower = log_with_terms(&[1, 1, 2, 2]);
+        let leader = log_with_terms(&[1, 1, 3, 3, 3]);
+
+        //Act
+        let plan = follower.reconcile_plan(&leader);
+
+        //Assert
+        assert_eq!(Some(3), plan.truncate_from);
+        assert_eq!(&[entry(3), entry(3), entry(3)][..], plan.entries);
+    }
+
+    #[test]
+    fn reconcile_plan_only_appends_missing_entries() {
+        //Arrange
+        let follower = log_with_terms(&[1, 1]);
+        let leader = log_with_terms(&[1, 1, 2, 2]);
+
+        //Act
+        let plan = follower.reconcile_plan(&leader);
+
+        //Assert
+        assert_eq!(None, plan.truncate_from);
+        assert_eq!(&[entry(2), entry(2)][..], plan.entries);
+        assert_eq!(
+            ReconcilePlan {
+                truncate_from: None,
+                entries: &[],
+            },
+            leader.reconcile_plan(&leader)
+        );
+    }
+
+    #[test]
+    fn assert_applicable_accepts_committed_range() {
+        //Arrange
+        let log = populated_log();
+
+        //Assert
+        assert_eq!(Ok(()), log.assert_applicable(1, 4, 4));
+        assert_eq!(Ok(()), log.assert_applicable(3, 3, 5));
+    }
+
+    #[test]
+    fn assert_applicable_rejects_invalid_ranges() {
+        //Arrange
+        let mut log = populated_log();
+
+        //Assert
+        assert_eq!(
+            Err(ApplyError::BeyondCommit{to: 5, commit_index: 4}),
+            log.assert_applicable(1, 5, 4)
+        );
+        assert_eq!(Err(ApplyError::Gap(6)), log.assert_applicable(4, 7, 7));
+        log.replace_all(
+            &SnapshotMeta {
+                last_included_index: 3,
+                last_included_term: 2,
+                configuration: servers!(1),
+            },
+            vec![entry(3)],
+        );
+        assert_eq!(
+            Err(ApplyError::BelowBase{from: 2, base_index: 3}),
+            log.assert_applicable(2, 4, 4)
+        );
+        assert_eq!(Ok(()), log.assert_applicable(4, 4, 4));
+    }
+
+    #[test]
+    fn last_index_and_term_fall_back_to_snapshot_once_fully_compacted() {
+        //Arrange
+        let mut log = populated_log();
+        assert_eq!((5, 3), (log.last_index(), log.last_term()));
+
+        //Act
+        log.compact(5);
+
+        //Assert
+        assert_eq!(5, log.last_index());
+        assert_eq!(3, log.last_term());
+        assert!(log.get(5).is_none());
+        assert_eq!(6, log.append(entry(4)));
+    }
+
+    #[test]
+    fn last_index_and_term_of_installed_snapshot() {
+        //Arrange
+        let mut log = populated_log();
+
+        //Act
+        log.replace_all(
+            &SnapshotMeta {
+                last_included_index: 12,
+                last_included_term: 6,
+                configuration: servers!(1),
+            },
+            Vec::new(),
+        );
+
+        //Assert
+        assert_eq!(12, log.last_index());
+        assert_eq!(6, log.last_term());
+        assert_eq!((0, 0), (Log::<Payload>::new().last_index(), Log::<Payload>::new().last_term()));
+    }
+
+    #[test]
+    fn pending_entries_yields_uncommitted_tail() {
+        //Arrange
+        let mut log = populated_log();
+        log.compact(1);
+
+        //Act
+        let pending = log.pending_entries(3).collect::<Vec<_>>();
+        let committed = log.iter_committed(3).collect::<Vec<_>>();
+
+        //Assert
+        assert_eq!(vec![(4, &entry(2)), (5, &entry(3))], pending);
+        assert_eq!(vec![(2, &entry(1)), (3, &entry(2))], committed);
+        assert_eq!(0, log.pending_entries(5).count());
+    }
+
+    #[test]
+    fn contains_term_finds_present_terms() {
+        let log = log_with_terms(&[2, 2, 4, 4, 4, 7]);
+        assert!(log.contains_term(2));
+        assert!(log.contains_term(4));
+        assert!(log.contains_term(7));
+    }
+
+    #[test]
+    fn contains_term_rejects_absent_terms() {
+        let log = log_with_terms(&[2, 2, 4, 4, 4, 7]);
+        assert!(!log.contains_term(1));
+        assert!(!log.contains_term(3));
+        assert!(!log.contains_term(8));
+        assert!(!Log::<Payload>::new().contains_term(0));
+    }
+
+    #[test]
+    fn contains_term_includes_compacted_base_term() {
+        //Arrange
+        let mut log = log_with_terms(&[2, 2, 4, 4, 4, 7]);
+
+        //Act
+        log.compact(4);
+
+        //Assert
+        assert!(!log.contains_term(2));
+        assert!(log.contains_term(4));
+        assert!(log.contains_term(7));
+    }
+
+    fn configuration(old_configuration: HashSet<ServerId>, configuration: HashSet<ServerId>) -> LogEntry<Payload> {
+        LogEntry {
+            term: 1,
+            command: Some(Command::SingleConfiguration{
+                old_configuration,
+                configuration,
+                learners: servers!(),
+                change_id: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn find_redundant_configs_flags_repeated_configuration() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(configuration(servers!(1, 2), servers!(1, 2, 3)));
+        log.append(entry(1));
+        log.append(configuration(servers!(1, 2, 3), servers!(1, 2, 3)));
+        log.append(configuration(servers!(1, 2, 3), servers!(2, 3)));
+
+        //Act
+        let redundant = log.find_redundant_configs();
+
+        //Assert
+        assert_eq!(vec![3], redundant);
+    }
+
+    #[test]
+    fn find_redundant_configs_accepts_meaningful_changes() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(configuration(servers!(1, 2), servers!(1, 2, 3)));
+        log.append(LogEntry {
+            term: 1,
+            command: Some(Command::JointConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                new_configuration: servers!(3, 4, 5),
+                change_id: None,
+            }),
+        });
+        log.append(configuration(servers!(1, 2, 3), servers!(3, 4, 5)));
+
+        //Act
+        let redundant = log.find_redundant_configs();
+
+        //Assert
+        assert!(redundant.is_empty());
+    }
+
+    #[test]
+    fn rewind_to_resets_entries_and_applied_watermark() {
+        //Arrange
+        struct Ignore;
+        impl StateMachine<Payload> for Ignore {
+            type Response = ();
+
+            fn apply(&mut self, _index: usize, _entry: &LogEntry<Payload>) {}
+        }
+        let mut log = populated_log();
+        log.apply_committed(&mut Ignore, 5);
+
+        //Act
+        log.rewind_to(3, 2);
+
+        //Assert
+        assert_eq!(3, log.last_index());
+        assert_eq!(2, log.last_term());
+        assert_eq!(2, log.last_applied());
+        assert!(log.get(4).is_none());
+        assert_eq!(4, log.append(entry(4)));
+    }
+
+    #[test]
+    #[should_panic(expected = "applied watermark 4 is beyond rewind index 3")]
+    fn rewind_to_rejects_watermark_beyond_index() {
+        //Arrange
+        let mut log = populated_log();
+
+        //Act
+        log.rewind_to(3, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "applied watermark 1 is below the snapshot base 2")]
+    fn rewind_to_rejects_watermark_below_base() {
+        //Arrange
+        let mut log = populated_log();
+        log.compact(2);
+
+        //Act
+        log.rewind_to(3, 1);
+    }
+
+    #[test]
+    fn is_change_committed_once_tagged_entry_commits() {
+        //Arrange
+        let mut log = populated_log();
+        let index = log.append(LogEntry {
+            term: 3,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 2, 3, 4),
+                learners: servers!(),
+                change_id: Some(17),
+            }),
+        });
+
+        //Act
+        let before = log.is_change_committed(17, index - 1);
+        let after = log.is_change_committed(17, index);
+
+        //Assert
+        assert!(!before);
+        assert!(after);
+        assert!(!log.is_change_committed(18, index));
+    }
+
+    #[test]
+    fn reserve_then_fill_replaces_placeholder() {
+        //Arrange
+        let mut log = populated_log();
+        let index = log.reserve(4);
+        log.append(entry(4));
+
+        //Act
+        let filled = log.fill(index, Command::Custom(Payload(9)), 5);
+
+        //Assert
+        assert_eq!(6, index);
+        assert_eq!(Ok(()), filled);
+        assert_eq!(
+            Some(&LogEntry {
+                term: 4,
+                command: Some(Command::Custom(Payload(9))),
+            }),
+            log.get(6)
+        );
+        assert_eq!(Some(&entry(4)), log.get(7));
+    }
+
+    #[test]
+    fn fill_rejects_committed_and_unreserved_slots() {
+        //Arrange
+        let mut log = populated_log();
+        let index = log.reserve(4);
+
+        //Assert
+        assert_eq!(
+            Err(LogError::Committed{index, commit_index: index}),
+            log.fill(index, Command::Custom(Payload(9)), index)
+        );
+        assert_eq!(Err(LogError::NotReserved(5)), log.fill(5, Command::Custom(Payload(9)), 4));
+        assert_eq!(Ok(()), log.fill(index, Command::Custom(Payload(9)), 5));
+        assert_eq!(
+            Err(LogError::NotReserved(index)),
+            log.fill(index, Command::Custom(Payload(10)), 5)
+        );
+    }
+
+    #[test]
+    fn fill_rejects_reserved_slot_behind_the_tail() {
+        //Arrange
+        let mut log = populated_log();
+        let first = log.reserve(4);
+        let second = log.reserve(4);
+
+        //Act
+        let behind = log.fill(first, Command::Custom(Payload(9)), 5);
+
+        //Assert
+        assert_eq!(Err(LogError::NotTailReserved{index: first, tail: second}), behind);
+        assert_eq!(Some(&LogEntry{term: 4, command: None}), log.get(first));
+        assert_eq!(Ok(()), log.fill(second, Command::Custom(Payload(10)), 5));
+        assert_eq!(Ok(()), log.fill(first, Command::Custom(Payload(9)), 5));
+    }
+
+    #[test]
+    fn apply_committed_reports_progress_every_interval() {
+        //Arrange
+        struct Ignore;
+        impl StateMachine<Payload> for Ignore {
+            type Response = ();
+
+            fn apply(&mut self, _index: usize, _entry: &LogEntry<Payload>) {}
+        }
+        let mut log = Log::new();
+        for _ in 0..5000 {
+            log.append(entry(1));
+        }
+        let mut reports = Vec::new();
+
+        //Act
+        log.apply_committed_with_progress(&mut Ignore, 5000, 1000, |applied, total| {
+            reports.push((applied, total));
+        });
+
+        //Assert
+        assert_eq!(
+            vec![(1000, 5000), (2000, 5000), (3000, 5000), (4000, 5000), (5000, 5000)],
+            reports
+        );
+        assert_eq!(5000, log.last_applied());
+    }
+
+    #[test]
+    fn empty_log_round_trips_through_json() {
+        //Arrange
+        let log = Log::<Payload>::new();
+
+        //Act
+        let encoded = log.to_json();
+        let decoded = Log::<Payload>::from_json(&encoded);
+
+        //Assert
+        assert_eq!(
+            serde_json::json!({
+                "lastIncludedIndex": 0,
+                "lastIncludedTerm": 0,
+                "entries": [],
+            }),
+            encoded
+        );
+        assert_eq!(Ok(log), decoded);
+    }
+
+    #[test]
+    fn compacted_log_round_trips_through_json() {
+        //Arrange
+        let mut log = populated_log();
+        log.compact(2);
+        log.append(LogEntry {
+            term: 3,
+            command: Some(Command::Custom(Payload(7))),
+        });
+
+        //Act
+        let decoded = Log::<Payload>::from_json(&log.to_json());
+
+        //Assert
+        assert_eq!(Ok(log), decoded);
+        assert_eq!(
+            Err(LogEntryError::MissingField("lastIncludedIndex")),
+            Log::<Payload>::from_json(&serde_json::json!({"entries": []}))
+        );
+    }
+
+    #[test]
+    fn committed_configuration_is_limited_by_commit_index() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(configuration(servers!(), servers!(1, 2, 3)));
+        log.append(entry(1));
+        log.append(configuration(servers!(1, 2, 3), servers!(1, 2, 3, 4)));
+        log.append(entry(1));
+        log.append(configuration(servers!(1, 2, 3, 4), servers!(2, 3, 4)));
+
+        //Assert
+        assert_eq!(Some(servers!(1, 2, 3, 4)), log.committed_configuration(5, 4));
+        assert_eq!(Some(servers!(1, 2, 3)), log.committed_configuration(2, 5));
+        assert_eq!(Some(servers!(2, 3, 4)), log.committed_configuration(5, 5));
+        assert_eq!(None, log.committed_configuration(5, 0));
+    }
+
+    #[test]
+    fn committed_configuration_survives_compaction() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(configuration(servers!(), servers!(1, 2, 3)));
+        log.append(entry(1));
+        log.append(entry(1));
+        log.append(entry(1));
+        log.compact(2);
+        log.compact(3);
+
+        //Act
+        let restored = Log::<Payload>::from_json(&log.to_json()).unwrap();
+
+        //Assert
+        assert_eq!(3, log.base_index());
+        assert_eq!(Some(servers!(1, 2, 3)), log.committed_configuration(4, 4));
+        assert_eq!(Some(servers!(1, 2, 3)), restored.committed_configuration(4, 4));
+    }
+}
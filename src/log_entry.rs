@@ -1,135 +1,813 @@
+use crate::{
+    base64,
+    configuration::Configuration,
+    decode::{decode_joint_configuration, decode_single_configuration, DecodeOptions, LogEntryError},
+    server_id::ServerId,
+};
+#[cfg(feature = "serde-derive")]
+use serde::{Deserialize, Serialize};
 use serde_json::{
     json,
     Value as JsonValue
 };
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     convert::TryFrom,
-    fmt::Debug
+    error::Error,
+    fmt::Debug,
+    hash::{Hash, Hasher},
 };
+use thiserror::Error;
 
-trait CustomCommand{
+pub trait CustomCommand{
     fn command_type(&self) -> & 'static str;
     fn to_json(&self) -> JsonValue;
-    fn from_json(json: &JsonValue) -> Self;
+    // Receives the whole entry. A malformed body is reported as
+    // `CommandDecodeError::Custom`.
+    fn from_json(json: &JsonValue) -> Result<Self, CommandDecodeError> where Self: Sized;
+    // Like `from_json`, but given the entry to consume, so a command can move
+    // parts of its body out rather than clone them.
+    fn from_json_owned(json: JsonValue) -> Result<Self, CommandDecodeError> where Self: Sized {
+        Self::from_json(&json)
+    }
+    // The client that submitted the command, for logs that index commands
+    // by client.
+    fn client_id(&self) -> Option<usize> {
+        None
+    }
+    // The payload of the binary encoding. By default this is the JSON body,
+    // so only commands with a tighter representation need to override it.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_json().to_string().into_bytes()
+    }
+    fn from_bytes(bytes: &[u8]) -> Result<Self, CommandDecodeError> where Self: Sized {
+        let command: JsonValue = serde_json::from_slice(bytes)
+            .map_err(|error| CommandDecodeError::Custom(Box::new(error)))?;
+        Self::from_json(&json!({
+            "command": command,
+        }))
+    }
+}
+
+// Boxed commands of different types can encode identical payloads, so the
+// command type is compared before the payloads are.
+impl PartialEq for dyn CustomCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.command_type() == other.command_type()
+            && self.to_json() == other.to_json()
+    }
 }
 
-#[derive(Eq)]
-enum Command<T>{
+// With the `serde-derive` feature, commands serialize to the same document
+// as `to_json`, split between the entry's `type` and `command` fields. A
+// custom command is written as-is, so `T` is expected to supply its own
+// `type` and `command` fields.
+#[cfg_attr(feature = "serde-derive", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde-derive",
+    serde(tag = "type", content = "command", rename_all_fields = "camelCase")
+)]
+pub enum Command<T>{
     SingleConfiguration{
-        old_configuration: HashSet<usize>,
-        configuration: HashSet<usize>
+        #[cfg_attr(feature = "serde-derive", serde(with = "instance_ids"))]
+        old_configuration: HashSet<ServerId>,
+        #[cfg_attr(feature = "serde-derive", serde(with = "instance_ids"))]
+        configuration: HashSet<ServerId>,
+        // Non-voting members that receive entries while they catch up.
+        #[cfg_attr(
+            feature = "serde-derive",
+            serde(default, skip_serializing_if = "HashSet::is_empty", with = "instance_ids")
+        )]
+        learners: HashSet<ServerId>,
+        // Lets a client track when its membership change commits; not part
+        // of the configuration itself, so ignored by equality.
+        #[cfg_attr(feature = "serde-derive", serde(default, skip_serializing_if = "Option::is_none"))]
+        change_id: Option<u64>,
     },
     JointConfiguration{
-        old_configuration: HashSet<usize>,
-        new_configuration: HashSet<usize>    
+        #[cfg_attr(feature = "serde-derive", serde(with = "instance_ids"))]
+        old_configuration: HashSet<ServerId>,
+        #[cfg_attr(feature = "serde-derive", serde(with = "instance_ids"))]
+        new_configuration: HashSet<ServerId>,
+        #[cfg_attr(feature = "serde-derive", serde(default, skip_serializing_if = "Option::is_none"))]
+        change_id: Option<u64>,
     },
+    // Custom commands committed as one entry and applied together, in
+    // order. Configuration changes must stand alone, so a batch never holds
+    // one.
+    #[cfg_attr(feature = "serde-derive", serde(with = "batch_entries"))]
+    Batch(Vec<Command<T>>),
+    // Appended by a new leader at the start of its term, so that entries
+    // from earlier terms commit along with it (Raft §8).
+    NoOp,
+    // Opaque bytes for state machines that have no command type of their
+    // own, written as base64 under `data`.
+    #[cfg_attr(feature = "serde-derive", serde(with = "client_data"))]
+    ClientData(Vec<u8>),
+    #[cfg_attr(feature = "serde-derive", serde(untagged))]
     Custom(T),
 }
 
 impl<T: CustomCommand> Command <T> {
 
-    fn command_type(&self) -> &str {
+    pub fn custom(custom_command: T) -> Self {
+        Command::Custom(custom_command)
+    }
+
+    pub fn command_type(&self) -> &str {
         match self{ 
             Command::SingleConfiguration{..} => "SingleConfiguration",
             Command::JointConfiguration{..} =>  "JointConfiguration",
+            Command::Batch(_) => "Batch",
+            Command::NoOp => "NoOp",
+            Command::ClientData(_) => "ClientData",
             Command::Custom(custom_command) => custom_command.command_type(),
+        }
     }
- 
-    fn to_json(&self) -> JsonValue{
+
+    pub fn to_json(&self) -> JsonValue{
         match self{ 
-            Command::SingleConfiguration{configuration, old_configuration} => {
-                        let mut configuration = configuration
-                        .iter()
-                        .copied()
-                        .collect::<Vec<_>>();
-            
-                    configuration.sort_unstable();
-            
-                    let mut old_configuration = old_configuration
-                        .iter()
-                        .copied()
-                        .collect::<Vec<_>>();
-            
-                    old_configuration.sort_unstable();
-            
-                    json!({
-                        "configuration":{
-                            "instanceIds": configuration
-                        },
-                        "oldConfiguration":{
-                            "instanceIds": old_configuration
-                                
-                        },
-            
-                    })
-            },
-            Command::JointConfiguration{new_configuration, old_configuration} =>  {
-                    let mut new_configuration = new_configuration
-                    .iter()
-                    .copied()
-                    .collect::<Vec<_>>();
-        
-                configuration.sort_unstable();
-        
-                let mut old_configuration = old_configuration
-                    .iter()
-                    .copied()
-                    .collect::<Vec<_>>();
-        
-                old_configuration.sort_unstable();
-        
-                json!({
-                    "newConfiguration":{
-                        "instanceIds": new_configuration
-                    },
-                    "oldConfiguration":{
-                        "instanceIds": old_configuration
-                            
-                    },
-        
-                })
+            Command::SingleConfiguration{configuration, old_configuration, learners, change_id} => {
+                encode_single_configuration(
+                    &sorted_instance_ids(old_configuration),
+                    &sorted_instance_ids(configuration),
+                    &sorted_instance_ids(learners),
+                    *change_id
+                )
             },
+            Command::JointConfiguration{new_configuration, old_configuration, change_id} =>  {
+                encode_joint_configuration(
+                    &sorted_instance_ids(old_configuration),
+                    &sorted_instance_ids(new_configuration),
+                    *change_id
+                )
+            },
+            Command::Batch(commands) => json!({
+                "entries": commands.iter().map(Command::to_batch_entry).collect::<Vec<_>>(),
+            }),
+            Command::NoOp => json!({}),
+            Command::ClientData(data) => json!({
+                "data": base64::encode(data),
+            }),
             Command::Custom(custom_command) => custom_command.to_json(),
          }  
+    }
+
+    fn to_batch_entry(&self) -> JsonValue {
+        json!({
+            "type": self.command_type(),
+            "command": self.to_json(),
+        })
+    }
+
+    // A one-line summary for operator logs, such as `add server 7 (now
+    // 1,2,3,7)` or `joint 1,2,3 -> 2,3,4`.
+    pub fn describe(&self) -> String {
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration, ..} => {
+                let added = sorted_instance_ids(&(configuration - old_configuration));
+                let removed = sorted_instance_ids(&(old_configuration - configuration));
+                let now = join_instance_ids(configuration);
+                match (added.as_slice(), removed.as_slice()) {
+                    ([added], []) => format!("add server {} (now {})", added, now),
+                    ([], [removed]) => format!("remove server {} (now {})", removed, now),
+                    _ if old_configuration.is_empty() => format!("configuration {}", now),
+                    _ => format!("configuration {} -> {}", join_instance_ids(old_configuration), now),
+                }
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => format!(
+                "joint {} -> {}",
+                join_instance_ids(old_configuration),
+                join_instance_ids(new_configuration)
+            ),
+            Command::Batch(commands) if commands.len() == 1 => String::from("batch of 1 command"),
+            Command::Batch(commands) => format!("batch of {} commands", commands.len()),
+            Command::NoOp => String::from("no-op"),
+            Command::ClientData(data) if data.len() == 1 => String::from("client data (1 byte)"),
+            Command::ClientData(data) => format!("client data ({} bytes)", data.len()),
+            Command::Custom(custom_command) => String::from(custom_command.command_type()),
+        }
+    }
+}
+
+// The sorted ids joined with commas, as in `1,2,3`.
+fn join_instance_ids(instance_ids: &HashSet<ServerId>) -> String {
+    sorted_instance_ids(instance_ids)
+        .iter()
+        .map(ServerId::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub(crate) fn sorted_instance_ids(instance_ids: &HashSet<ServerId>) -> Vec<ServerId> {
+    let mut instance_ids = instance_ids
+        .iter()
+        .copied()
+        .collect::<Vec<_>>();
+    instance_ids.sort_unstable();
+    instance_ids
+}
+
+// Reads and writes an id set as `{"instanceIds": [...]}`, sorted, matching
+// `encode_single_configuration` and friends.
+#[cfg(feature = "serde-derive")]
+mod instance_ids {
+    use super::{sorted_instance_ids, ServerId};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashSet;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct InstanceIds {
+        instance_ids: Vec<ServerId>,
+    }
+
+    pub fn serialize<S: Serializer>(instance_ids: &HashSet<ServerId>, serializer: S) -> Result<S::Ok, S::Error> {
+        InstanceIds{
+            instance_ids: sorted_instance_ids(instance_ids),
+        }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashSet<ServerId>, D::Error> {
+        InstanceIds::deserialize(deserializer)
+            .map(|configuration| configuration.instance_ids.into_iter().collect())
+    }
+}
+
+// Reads and writes client data as `{"data": "<base64>"}`.
+#[cfg(feature = "serde-derive")]
+mod client_data {
+    use crate::base64;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct ClientData {
+        data: String,
+    }
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        ClientData{
+            data: base64::encode(data),
+        }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let client_data = ClientData::deserialize(deserializer)?;
+        base64::decode(&client_data.data).ok_or_else(|| D::Error::custom("client data is not valid base64"))
+    }
+}
+
+// Reads and writes the commands of a batch as `{"entries": [...]}`.
+#[cfg(feature = "serde-derive")]
+mod batch_entries {
+    use super::Command;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct BatchRef<'a, T> {
+        entries: &'a [Command<T>],
+    }
+
+    #[derive(Deserialize)]
+    struct Batch<T> {
+        entries: Vec<Command<T>>,
+    }
+
+    pub fn serialize<S: Serializer, T: Serialize>(entries: &[Command<T>], serializer: S) -> Result<S::Ok, S::Error> {
+        BatchRef{entries}.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Vec<Command<T>>, D::Error> {
+        Batch::deserialize(deserializer).map(|batch| batch.entries)
+    }
+}
+
+pub(crate) fn encode_single_configuration(
+    old_configuration: &[ServerId],
+    configuration: &[ServerId],
+    learners: &[ServerId],
+    change_id: Option<u64>,
+) -> JsonValue {
+    let mut command = json!({
+        "configuration":{
+            "instanceIds": configuration
+        },
+        "oldConfiguration":{
+            "instanceIds": old_configuration
+        },
+    });
+    if !learners.is_empty() {
+        command["learners"] = json!({
+            "instanceIds": learners
+        });
+    }
+    with_change_id(command, change_id)
+}
+
+pub(crate) fn encode_joint_configuration(
+    old_configuration: &[ServerId],
+    new_configuration: &[ServerId],
+    change_id: Option<u64>,
+) -> JsonValue {
+    with_change_id(
+        json!({
+            "newConfiguration":{
+                "instanceIds": new_configuration
+            },
+            "oldConfiguration":{
+                "instanceIds": old_configuration
+            },
+        }),
+        change_id
+    )
+}
+
+fn with_change_id(mut command: JsonValue, change_id: Option<u64>) -> JsonValue {
+    if let (JsonValue::Object(command), Some(change_id)) = (&mut command, change_id) {
+        command.insert(String::from("changeId"), JsonValue::from(change_id));
+    }
+    command
+}
+
+fn decode_client_data(command: &JsonValue) -> Option<Vec<u8>> {
+    command
+        .get("data")
+        .and_then(JsonValue::as_str)
+        .and_then(base64::decode)
+}
+
+pub(crate) fn decode_change_id(command: &JsonValue) -> Option<u64> {
+    command.get("changeId").and_then(JsonValue::as_u64)
+}
+
+// The voter sets of a single configuration, borrowed from the command, for
+// code that only reads the single-configuration shape.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SingleConfigView<'a> {
+    pub voters: &'a HashSet<ServerId>,
+    pub old_voters: &'a HashSet<ServerId>,
+}
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum ConfigError {
+    #[error("configuration change neither adds nor removes a server")]
+    NoChange,
+    #[error("server {0} is already a member of the configuration")]
+    AlreadyMember(ServerId),
+    #[error("server {0} is not a member of the configuration")]
+    NotMember(ServerId),
+    #[error("server {0} is not a learner")]
+    NotLearner(ServerId),
+    #[error("a batch cannot contain a configuration change")]
+    ConfigurationInBatch,
+    #[error("removing server {0} would leave the cluster without members")]
+    LastServer(ServerId),
+    #[error("configuration is not joint")]
+    NotJoint,
+}
+
+impl<T> Command<T> {
+    // Adds a server, removes one, or both for a replace, which keeps the
+    // cluster's size and so its quorum size.
+    pub fn single_server_change(
+        current: &HashSet<ServerId>,
+        add: Option<ServerId>,
+        remove: Option<ServerId>,
+    ) -> Result<Self, ConfigError> {
+        if add.is_none() && remove.is_none() {
+            return Err(ConfigError::NoChange);
+        }
+        let mut configuration = current.clone();
+        if let Some(remove) = remove {
+            if !configuration.remove(&remove) {
+                return Err(ConfigError::NotMember(remove));
+            }
+        }
+        if let Some(add) = add {
+            if current.contains(&add) {
+                return Err(ConfigError::AlreadyMember(add));
+            }
+            configuration.insert(add);
+        }
+        Ok(Command::SingleConfiguration{
+            old_configuration: current.clone(),
+            configuration,
+            learners: HashSet::new(),
+            change_id: None,
+        })
+    }
+
+    pub fn batch(commands: Vec<Command<T>>) -> Result<Self, ConfigError> {
+        if commands.iter().any(Command::is_configuration) {
+            return Err(ConfigError::ConfigurationInBatch);
+        }
+        Ok(Command::Batch(commands))
+    }
+
+    // Whether this command changes the configuration, either directly or
+    // through a batch.
+    fn is_configuration(&self) -> bool {
+        match self {
+            Command::SingleConfiguration{..} | Command::JointConfiguration{..} => true,
+            Command::Batch(commands) => commands.iter().any(Command::is_configuration),
+            Command::NoOp | Command::ClientData(_) | Command::Custom(_) => false,
+        }
+    }
+
+    // The configuration without `id`, such as a leader removing itself.
+    pub fn remove_server(current: &HashSet<ServerId>, id: ServerId) -> Result<Self, ConfigError> {
+        if current.len() == 1 && current.contains(&id) {
+            return Err(ConfigError::LastServer(id));
+        }
+        Self::single_server_change(current, None, Some(id))
+    }
+
+    // A configuration that returns the voters to `prior`, for abandoning a
+    // change before it commits. `Log::append_revert` sets the voters being
+    // reverted with `with_old_configuration`.
+    pub fn revert_to(prior: &HashSet<ServerId>) -> Command<T> {
+        Command::SingleConfiguration{
+            old_configuration: HashSet::new(),
+            configuration: prior.clone(),
+            learners: HashSet::new(),
+            change_id: None,
+        }
+    }
+
+    // Moves a caught-up learner into the voters of a single configuration.
+    pub fn promote(&self, learner_id: ServerId) -> Result<Command<T>, ConfigError> {
+        match self {
+            Command::SingleConfiguration{configuration, learners, ..} if learners.contains(&learner_id) => {
+                let mut learners = learners.clone();
+                learners.remove(&learner_id);
+                let mut new_configuration = configuration.clone();
+                new_configuration.insert(learner_id);
+                Ok(Command::SingleConfiguration{
+                    old_configuration: configuration.clone(),
+                    configuration: new_configuration,
+                    learners,
+                    change_id: None,
+                })
+            },
+            _ => Err(ConfigError::NotLearner(learner_id)),
+        }
+    }
+
+    // Sets the configuration being changed from; other commands are returned
+    // unchanged.
+    pub fn with_old_configuration(self, old: HashSet<ServerId>) -> Command<T> {
+        match self {
+            Command::SingleConfiguration{configuration, learners, change_id, ..} => Command::SingleConfiguration{
+                old_configuration: old,
+                configuration,
+                learners,
+                change_id,
+            },
+            Command::JointConfiguration{new_configuration, change_id, ..} => Command::JointConfiguration{
+                old_configuration: old,
+                new_configuration,
+                change_id,
+            },
+            other @ (Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_)) => other,
+        }
+    }
+
+    // A copy to compare, hash or serialize in place of the original. Id sets
+    // have no duplicates and every encoding and `Hash` writes them sorted,
+    // so the copy is the same command; normalizing through here keeps that
+    // guarantee in one place should the representation change.
+    pub fn canonicalize(&self) -> Command<T>
+    where
+        T: Clone,
+    {
+        let canonical = |instance_ids: &HashSet<ServerId>| sorted_instance_ids(instance_ids).into_iter().collect();
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration, learners, change_id} => Command::SingleConfiguration{
+                old_configuration: canonical(old_configuration),
+                configuration: canonical(configuration),
+                learners: canonical(learners),
+                change_id: *change_id,
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, change_id} => Command::JointConfiguration{
+                old_configuration: canonical(old_configuration),
+                new_configuration: canonical(new_configuration),
+                change_id: *change_id,
+            },
+            Command::Batch(commands) => Command::Batch(commands.iter().map(Command::canonicalize).collect()),
+            other => other.clone(),
+        }
+    }
+
+    pub fn is_single(&self) -> bool {
+        matches!(self, Command::SingleConfiguration{..})
+    }
+
+    pub fn is_joint(&self) -> bool {
+        matches!(self, Command::JointConfiguration{..})
+    }
+
+    pub fn as_single_view(&self) -> Option<SingleConfigView<'_>> {
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration, ..} => Some(SingleConfigView{
+                voters: configuration,
+                old_voters: old_configuration,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn voters_count(&self) -> usize {
+        match self {
+            Command::SingleConfiguration{configuration, ..} => configuration.len(),
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => {
+                old_configuration.len()
+                    + new_configuration
+                        .iter()
+                        .filter(|instance_id| !old_configuration.contains(instance_id))
+                        .count()
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => 0,
+        }
+    }
+
+    // How many servers the configuration adds plus removes relative to
+    // `previous`, judged by the target voters of a joint configuration.
+    // Commands that are not configurations change nothing.
+    pub fn voter_change_magnitude(&self, previous: &HashSet<ServerId>) -> usize {
+        match self {
+            Command::SingleConfiguration{configuration: voters, ..}
+            | Command::JointConfiguration{new_configuration: voters, ..} => {
+                voters.symmetric_difference(previous).count()
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => 0,
+        }
+    }
+
+    // Whether `acks` form a quorum of this configuration; see
+    // `Configuration::has_quorum`. Non-configuration commands have no quorum.
+    pub fn has_quorum(&self, acks: &HashSet<ServerId>) -> bool {
+        Configuration::from_command(self).is_some_and(|configuration| configuration.has_quorum(acks))
+    }
+
+    // The servers the configuration drops from the voters, whose
+    // connections can be torn down once it takes effect.
+    pub fn departing_servers(&self) -> Option<HashSet<ServerId>> {
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration: new_configuration, ..}
+            | Command::JointConfiguration{old_configuration, new_configuration, ..} => {
+                Some(old_configuration - new_configuration)
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => None,
+        }
+    }
+
+    // Every server a configuration mentions, in ascending order: both sides
+    // of the change and any learners. Other commands mention none.
+    pub fn server_ids(&self) -> impl Iterator<Item = ServerId> {
+        let mut server_ids = BTreeSet::new();
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration, learners, ..} => {
+                server_ids.extend(old_configuration.iter().chain(configuration).chain(learners));
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => {
+                server_ids.extend(old_configuration.iter().chain(new_configuration));
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => {},
+        }
+        server_ids.into_iter()
+    }
+
+    // The servers whose votes count while this configuration is in effect:
+    // both sets of a joint configuration, or `None` for non-configuration
+    // commands.
+    pub fn active_voters(&self) -> Option<HashSet<ServerId>> {
+        match self {
+            Command::SingleConfiguration{configuration, ..} => Some(configuration.clone()),
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => {
+                Some(old_configuration | new_configuration)
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => None,
+        }
+    }
+
+    pub fn change_id(&self) -> Option<u64> {
+        match self {
+            Command::SingleConfiguration{change_id, ..}
+            | Command::JointConfiguration{change_id, ..} => *change_id,
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => None,
+        }
+    }
+
+    // A short, order-independent rendering of the configuration, such as
+    // `single:[42,85]` or `joint:[1,2]->[2,3]`, for logging and quick
+    // comparisons.
+    pub fn config_fingerprint(&self) -> String {
+        let render = |instance_ids: &HashSet<ServerId>| format!("[{}]", join_instance_ids(instance_ids));
+        match self {
+            Command::SingleConfiguration{configuration, learners, ..} if learners.is_empty() => {
+                format!("single:{}", render(configuration))
+            },
+            Command::SingleConfiguration{configuration, learners, ..} => {
+                format!("single:{}+learners:{}", render(configuration), render(learners))
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => {
+                format!("joint:{}->{}", render(old_configuration), render(new_configuration))
+            },
+            Command::Batch(_) => String::from("batch"),
+            Command::NoOp => String::from("noop"),
+            Command::ClientData(_) => String::from("data"),
+            Command::Custom(_) => String::from("custom"),
+        }
+    }
+
+    // A structured record of the configuration change committed at `index`,
+    // naming the server that requested it where known. Non-configuration
+    // commands have nothing to audit.
+    pub fn audit_record(&self, index: usize, term: u64, actor: Option<ServerId>) -> Option<JsonValue> {
+        let (old_configuration, new_configuration) = match self {
+            Command::SingleConfiguration{old_configuration, configuration, ..} => (old_configuration, configuration),
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => (old_configuration, new_configuration),
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => return None,
+        };
+        Some(json!({
+            "index": index,
+            "term": term,
+            "change": self.config_fingerprint(),
+            "added": sorted_instance_ids(&(new_configuration - old_configuration)),
+            "removed": sorted_instance_ids(&(old_configuration - new_configuration)),
+            "actor": actor,
+        }))
+    }
+
+    pub fn to_membership(&self) -> Option<Vec<ServerId>> {
+        self.active_voters()
+            .map(|voters| sorted_instance_ids(&voters))
+    }
+
+    pub fn from_membership(instance_ids: &[ServerId]) -> Self {
+        Command::SingleConfiguration{
+            old_configuration: HashSet::new(),
+            configuration: instance_ids.iter().copied().collect(),
+            learners: HashSet::new(),
+            change_id: None,
+        }
+    }
+
+    // Whether this configuration change takes `id` out of the cluster. A
+    // leader checks this against its own id when a configuration entry
+    // commits, and steps down if it has been removed: it keeps managing the
+    // cluster until the change commits, but no longer counts itself toward
+    // majorities of the new configuration.
+    pub fn removes_server(&self, id: ServerId) -> bool {
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration, ..} => {
+                old_configuration.contains(&id) && !configuration.contains(&id)
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => {
+                old_configuration.contains(&id) && !new_configuration.contains(&id)
+            },
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => false,
+        }
+    }
+
+    // Whether `leader_id` belongs to the configuration being moved to. A
+    // leader left out of a joint configuration's new set keeps leading
+    // until the change commits, but must not count its own vote toward the
+    // new configuration's majority.
+    pub fn leader_in_new_config(&self, leader_id: ServerId) -> bool {
+        match self {
+            Command::SingleConfiguration{configuration, ..} => configuration.contains(&leader_id),
+            Command::JointConfiguration{new_configuration, ..} => new_configuration.contains(&leader_id),
+            Command::Batch(_) | Command::NoOp | Command::ClientData(_) | Command::Custom(_) => false,
+        }
+    }
+
+    pub fn remap_ids(&self, f: impl Fn(ServerId) -> ServerId) -> Command<T>
+    where
+        T: Clone,
+    {
+        self.remap_ids_with(&f)
+    }
+
+    fn remap_ids_with(&self, f: &dyn Fn(ServerId) -> ServerId) -> Command<T>
+    where
+        T: Clone,
+    {
+        let remap = |instance_ids: &HashSet<ServerId>| {
+            instance_ids
+                .iter()
+                .map(|&instance_id| f(instance_id))
+                .collect()
+        };
+        match self {
+            Command::SingleConfiguration{old_configuration, configuration, learners, change_id} => Command::SingleConfiguration{
+                old_configuration: remap(old_configuration),
+                configuration: remap(configuration),
+                learners: remap(learners),
+                change_id: *change_id,
+            },
+            Command::JointConfiguration{old_configuration, new_configuration, change_id} => Command::JointConfiguration{
+                old_configuration: remap(old_configuration),
+                new_configuration: remap(new_configuration),
+                change_id: *change_id,
+            },
+            Command::Batch(commands) => Command::Batch(
+                commands
+                    .iter()
+                    .map(|command| command.remap_ids_with(f))
+                    .collect()
+            ),
+            Command::NoOp => Command::NoOp,
+            Command::ClientData(data) => Command::ClientData(data.clone()),
+            Command::Custom(custom_command) => Command::Custom(custom_command.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CommandDecodeError {
+    #[error("command has no `type`")]
+    MissingType,
+    #[error("unknown command type {0}")]
+    UnknownType(String),
+    #[error("configuration command has no `command` object")]
+    MalformedConfiguration,
+    #[error("batch has no `entries` array")]
+    MalformedBatch,
+    #[error("a batch cannot contain a configuration change")]
+    ConfigurationInBatch,
+    #[error("client data is not valid base64")]
+    MalformedClientData,
+    #[error("invalid configuration: {0}")]
+    InvalidConfiguration(LogEntryError),
+    #[error("invalid entry: {0}")]
+    InvalidEntry(LogEntryError),
+    #[error("custom command failed to decode: {0}")]
+    Custom(Box<dyn Error + Send + Sync>),
+}
 
+// The body of a configuration command, which must be an object.
+fn configuration_body(json: &JsonValue) -> Result<&JsonValue, CommandDecodeError> {
+    json.get("command")
+        .filter(|command| command.is_object())
+        .ok_or(CommandDecodeError::MalformedConfiguration)
+}
 
+// Decodes the built-in command types, handing any other type to `custom`
+// along with the whole entry.
+fn decode_command<T>(
+    json: &JsonValue,
+    custom: &dyn Fn(&str, &JsonValue) -> Result<T, CommandDecodeError>,
+) -> Result<Command<T>, CommandDecodeError> {
+    let command_type = match json.get("type") {
+        None => return Err(CommandDecodeError::MissingType),
+        Some(JsonValue::String(command_type)) => command_type.as_str(),
+        Some(command_type) => return Err(CommandDecodeError::UnknownType(command_type.to_string())),
+    };
+    match command_type {
+        "SingleConfiguration" => decode_single_configuration(configuration_body(json)?, &DecodeOptions::default())
+            .map_err(CommandDecodeError::InvalidConfiguration),
+        "JointConfiguration" => decode_joint_configuration(configuration_body(json)?, &DecodeOptions::default())
+            .map_err(CommandDecodeError::InvalidConfiguration),
+        "Batch" => {
+            let commands = json
+                .get("command")
+                .and_then(|command| command.get("entries"))
+                .and_then(JsonValue::as_array)
+                .ok_or(CommandDecodeError::MalformedBatch)?
+                .iter()
+                .map(|entry| decode_command(entry, custom))
+                .collect::<Result<Vec<_>, _>>()?;
+            Command::batch(commands).map_err(|_| CommandDecodeError::ConfigurationInBatch)
+        },
+        "NoOp" => Ok(Command::NoOp),
+        "ClientData" => json
+            .get("command")
+            .and_then(decode_client_data)
+            .map(Command::ClientData)
+            .ok_or(CommandDecodeError::MalformedClientData),
+        _ => custom(command_type, json).map(Command::Custom),
     }
 }
 
 impl <T: CustomCommand> TryFrom<&JsonValue> for Command<T>{
-    type Error = ();
+    type Error = CommandDecodeError;
     fn try_from(json: &JsonValue) -> Result<Self, Self::Error> {
-        json.get("type").and_then(JsonValue::as_str)
-        .and_then(|command| 
-        match command{
-            "SingleConfiguration" => json.get("command").map(|command| {
-                    Command::SingleConfiguration{
-                        configuration: command
-                            .get("configuration")
-                            .map(decode_instance_ids)
-                            .unwrap_or_else(HashSet::new),
-                        old_configuration:command
-                            .get("oldConfiguration")
-                            .map(decode_instance_ids)
-                            .unwrap_or_else(HashSet::new),
-                        }
-                    }),
-                    "JointConfiguration" => json.get("command").map(|command| {
-                        Command::JointConfiguration{
-                            new_configuration: command
-                                .get("newConfiguration")
-                                .map(decode_instance_ids)
-                                .unwrap_or_else(HashSet::new),
-                            old_configuration:command
-                                .get("oldConfiguration")
-                                .map(decode_instance_ids)
-                                .unwrap_or_else(HashSet::new),
-                            }
-                        })
-                     _ => T::from_json(json),
-                    })
-                    .ok_or(())
+        decode_command(json, &|_, json| T::from_json(json))
+    }
+}
+
+// Built-in commands are rebuilt from their fields either way, so only a
+// custom command gains from owning the entry.
+impl <T: CustomCommand> TryFrom<JsonValue> for Command<T>{
+    type Error = CommandDecodeError;
+    fn try_from(json: JsonValue) -> Result<Self, Self::Error> {
+        match json.get("type").and_then(JsonValue::as_str) {
+            None | Some("SingleConfiguration" | "JointConfiguration" | "Batch" | "NoOp" | "ClientData") => {
+                Self::try_from(&json)
+            },
+            Some(_) => T::from_json_owned(json).map(Command::Custom),
+        }
     }
 }
 
@@ -139,33 +817,53 @@ impl <T: Debug> Debug for Command <T> {
             Self::SingleConfiguration{
                 old_configuration,
                 configuration,
+                ..
             } =>{
-                write!(&mut f, "SingleConfiguration({:?} -> {:?})", old_configuration, configuration)
+                write!(
+                    f,
+                    "SingleConfiguration({:?} -> {:?})",
+                    sorted_instance_ids(old_configuration),
+                    sorted_instance_ids(configuration)
+                )
             },
             Self::JointConfiguration {
-                old_configuration
-                new_configuration
+                old_configuration,
+                new_configuration,
+                ..
             } => {
-                write!(&mut f, "JointConfiguration({:?} -> {:?})", old_configuration, new_configuration)
+                write!(
+                    f,
+                    "JointConfiguration({:?} -> {:?})",
+                    sorted_instance_ids(old_configuration),
+                    sorted_instance_ids(new_configuration)
+                )
             },
+            Self::Batch(commands) => write!(f, "Batch({:?})", commands),
+            Self::NoOp => write!(f, "NoOp"),
+            Self::ClientData(data) => write!(f, "ClientData({} bytes)", data.len()),
             Self::Custom(custom_comment) => custom_comment.fmt(f),
         }
     }
 }
 
 impl <T: PartialEq> PartialEq  for Command <T> {
-    fn eq(&self, other: &self) -> bool{
+    fn eq(&self, other: &Self) -> bool{
         match self {
             Self::SingleConfiguration{
                 old_configuration,
                 configuration,
+                learners,
+                ..
             } =>{
                 if let Self::SingleConfiguration{
                     old_configuration: other_old_configuration,
-                    configuration: other_configuration
+                    configuration: other_configuration,
+                    learners: other_learners,
+                    ..
                 } = other {
                     old_configuration.eq(other_old_configuration)
                     && configuration.eq(other_configuration)
+                    && learners.eq(other_learners)
                 } else {
                     false
                 }
@@ -174,10 +872,12 @@ impl <T: PartialEq> PartialEq  for Command <T> {
             Self::JointConfiguration{
                 old_configuration,
                 new_configuration,
+                ..
             } =>{
                 if let Self::JointConfiguration{
                     old_configuration: other_old_configuration,
-                    new_configuration: other_new_configuration
+                    new_configuration: other_new_configuration,
+                    ..
                 } = other {
                     old_configuration.eq(other_old_configuration)
                     && new_configuration.eq(other_new_configuration)
@@ -186,105 +886,320 @@ impl <T: PartialEq> PartialEq  for Command <T> {
                 }
       
             },
-            Self::Custom(custom_comment) => custom_comment.eq(other),
+            Self::Batch(commands) => {
+                if let Self::Batch(other_commands) = other {
+                    commands.eq(other_commands)
+                } else {
+                    false
+                }
+            },
+            Self::NoOp => matches!(other, Self::NoOp),
+            Self::ClientData(data) => {
+                if let Self::ClientData(other_data) = other {
+                    data.eq(other_data)
+                } else {
+                    false
+                }
+            },
+            Self::Custom(custom_command) => {
+                if let Self::Custom(other_custom_command) = other {
+                    custom_command.eq(other_custom_command)
+                } else {
+                    false
+                }
+            },
         }
     }
     
 }
 
-#[derive(Debug, Eq, PartialEq)]
-struct LogEntry <T>{
-    term: usize,
-    command: Option<Command<T>>,
+impl <T: Eq> Eq for Command <T> {}
+
+impl <T: Clone> Clone for Command <T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::SingleConfiguration{old_configuration, configuration, learners, change_id} => Self::SingleConfiguration{
+                old_configuration: old_configuration.clone(),
+                configuration: configuration.clone(),
+                learners: learners.clone(),
+                change_id: *change_id,
+            },
+            Self::JointConfiguration{old_configuration, new_configuration, change_id} => Self::JointConfiguration{
+                old_configuration: old_configuration.clone(),
+                new_configuration: new_configuration.clone(),
+                change_id: *change_id,
+            },
+            Self::Batch(commands) => Self::Batch(commands.clone()),
+            Self::NoOp => Self::NoOp,
+            Self::ClientData(data) => Self::ClientData(data.clone()),
+            Self::Custom(custom_command) => Self::Custom(custom_command.clone()),
+        }
+    }
 }
 
-impl <T: CustomCommand> LogEntry <T>{
-    fn to_json(&self) -> JsonValue{
-        let mut json = serde_json::Map::new();
-        json.insert(String::from("term"), JsonValue::from(self.term));
-        if let Some(command) = &self.command {
-                json.insert(
-                        String::from("type"),
-                        JsonValue(command.command_type())
-                );
-                json.insert(String::from("command"), command.to_json();
-        } 
-        JsonValue::Object(json)
+// Consistent with `PartialEq`: change ids are not hashed, and id sets are
+// hashed sorted since `HashSet` iteration order is arbitrary.
+impl <T: Hash> Hash for Command <T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::SingleConfiguration{old_configuration, configuration, learners, ..} => {
+                sorted_instance_ids(old_configuration).hash(state);
+                sorted_instance_ids(configuration).hash(state);
+                sorted_instance_ids(learners).hash(state);
+            },
+            Self::JointConfiguration{old_configuration, new_configuration, ..} => {
+                sorted_instance_ids(old_configuration).hash(state);
+                sorted_instance_ids(new_configuration).hash(state);
+            },
+            Self::Batch(commands) => commands.hash(state),
+            Self::NoOp => {},
+            Self::ClientData(data) => data.hash(state),
+            Self::Custom(custom_command) => custom_command.hash(state),
+        }
     }
 }
 
-fn decode_instance_ids(configuration: &JsonValue) -> HashSet<usize> {
-    configuration
-        .get("instaceIds")
-        .and_then(JsonValue::as_array)
-        .map(|instance_ids|{
-            instance_ids
-            .iter()
-            .filter_map(JsonValue::as_u64)
-            .map(|value| value as usize)
-            .collect()
-        })
-        .unwrap_or_else(HashSet::new)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde-derive", derive(Serialize, Deserialize))]
+pub struct LogEntry <T>{
+    pub term: u64,
+    #[cfg_attr(feature = "serde-derive", serde(flatten))]
+    pub command: Option<Command<T>>,
+}
 
+impl<T> LogEntry<T> {
+    pub fn builder() -> LogEntryBuilder<T> {
+        LogEntryBuilder {
+            term: 0,
+            command: None,
+        }
     }
-}
 
-impl From<&JsonValue> for LogEntry{
-    fn from(json: &JsonValue) -> Self ({
-        Self {
-            term: json 
-              .get("term")
-              .and_then(JsonValue::as_u64)
-              .map(|term| term as usize)
-              .unwrap_or(0 ),
-            command:  Command::try_from(json).ok(),
-        }                     
+    pub fn term(&self) -> u64 {
+        self.term
+    }
+
+    pub fn is_config_change(&self) -> bool {
+        matches!(
+            self.command,
+            Some(Command::SingleConfiguration{..} | Command::JointConfiguration{..})
+        )
+    }
 
+    // Term 0 is reserved for the empty initial log, so no command can have
+    // been appended in it.
+    pub fn validate(&self) -> Result<(), LogEntryError> {
+        if self.term == 0 && self.command.is_some() {
+            return Err(LogEntryError::CommandAtTermZero);
+        }
+        Ok(())
     }
 }
 
+// Builds a `LogEntry`, starting from term 0 without a command.
+#[derive(Debug)]
+pub struct LogEntryBuilder<T> {
+    term: u64,
+    command: Option<Command<T>>,
+}
+
+impl<T> LogEntryBuilder<T> {
+    pub fn term(mut self, term: u64) -> Self {
+        self.term = term;
+        self
+    }
+
+    pub fn command(mut self, command: Command<T>) -> Self {
+        self.command = Some(command);
+        self
+    }
+
+    pub fn no_command(mut self) -> Self {
+        self.command = None;
+        self
+    }
+
+    pub fn build(self) -> LogEntry<T> {
+        LogEntry {
+            term: self.term,
+            command: self.command,
+        }
+    }
+}
+
+impl <T: CustomCommand> LogEntry <T>{
+    pub fn to_json(&self) -> JsonValue{
+        let mut json = serde_json::Map::new();
+        json.insert(String::from("term"), JsonValue::from(self.term));
+        if let Some(command) = &self.command {
+            json.insert(
+                String::from("type"),
+                JsonValue::from(command.command_type())
+            );
+            json.insert(String::from("command"), command.to_json());
+        }
+        JsonValue::Object(json)
+    }
+
+    // The length of the entry's JSON encoding, which is what a follower
+    // receives and what a snapshot policy weighs.
+    pub fn size_bytes(&self) -> usize {
+        self.to_json().to_string().len()
+    }
+}
+
+// Fails if the entry names a command type whose body does not decode. An
+// entry without a `type` has no command and succeeds.
+impl <T: CustomCommand> TryFrom<&JsonValue> for LogEntry<T>{
+    type Error = CommandDecodeError;
+
+    fn try_from(json: &JsonValue) -> Result<Self, Self::Error> {
+        entry_from_json(decode_term_field(json), Command::try_from(json))
+    }
+}
+
+impl <T: CustomCommand> TryFrom<JsonValue> for LogEntry<T>{
+    type Error = CommandDecodeError;
+
+    fn try_from(json: JsonValue) -> Result<Self, Self::Error> {
+        entry_from_json(decode_term_field(&json), Command::try_from(json))
+    }
+}
+
+fn decode_term_field(json: &JsonValue) -> u64 {
+    json.get("term")
+        .and_then(JsonValue::as_u64)
+        .unwrap_or(0)
+}
+
+// Every JSON conversion ends here: an entry without a `type` has no command,
+// and the entry must pass `validate`.
+fn entry_from_json<T>(term: u64, command: Result<Command<T>, CommandDecodeError>) -> Result<LogEntry<T>, CommandDecodeError> {
+    let command = match command {
+        Ok(command) => Some(command),
+        Err(CommandDecodeError::MissingType) => None,
+        Err(error) => return Err(error),
+    };
+    let entry = LogEntry{term, command};
+    entry.validate().map_err(CommandDecodeError::InvalidEntry)?;
+    Ok(entry)
+}
+
+type CustomCommandFactory<T> = Box<dyn Fn(&JsonValue) -> Option<T>>;
+
+// Decodes entries whose custom commands are built by closures registered per
+// command type, rather than through `CustomCommand::from_json`. Each closure
+// receives the entry's `command` object.
+pub struct LogEntryFactory<T> {
+    factories: HashMap<String, CustomCommandFactory<T>>,
+    fallback: Option<Box<LogEntryFactory<T>>>,
+}
+
+impl<T> LogEntryFactory<T> {
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&JsonValue) -> Option<T> + 'static,
+    {
+        self.factories.insert(String::from(name), Box::new(factory));
+    }
+
+    // Command types not registered with this factory are decoded by `other`.
+    pub fn with_fallback(mut self, other: LogEntryFactory<T>) -> Self {
+        self.fallback = Some(Box::new(match self.fallback.take() {
+            Some(fallback) => fallback.with_fallback(other),
+            None => other,
+        }));
+        self
+    }
+
+    fn decode_custom(&self, command_type: &str, command: &JsonValue) -> Result<T, CommandDecodeError> {
+        match (self.factories.get(command_type), &self.fallback) {
+            (Some(factory), _) => factory(command).ok_or_else(|| {
+                CommandDecodeError::Custom(format!("{} command failed to decode", command_type).into())
+            }),
+            (None, Some(fallback)) => fallback.decode_custom(command_type, command),
+            (None, None) => Err(CommandDecodeError::UnknownType(String::from(command_type))),
+        }
+    }
+
+    // Built-in command types decode as they do through `Command::try_from`;
+    // only custom types go through the registered closures. An entry
+    // without a `type` has no command. Failures are returned rather than
+    // collapsed to `None`, so a caller can tell an unregistered type
+    // (`UnknownType`) from a closure that rejected its command (`Custom`)
+    // or an entry that decoded but is invalid (`InvalidEntry`).
+    pub fn decode(&self, json: &JsonValue) -> Result<LogEntry<T>, CommandDecodeError> {
+        let decode_custom = |command_type: &str, json: &JsonValue| {
+            self.decode_custom(command_type, json.get("command").unwrap_or(&JsonValue::Null))
+        };
+        entry_from_json(decode_term_field(json), decode_command(json, &decode_custom))
+    }
+}
+
+impl<T> Default for LogEntryFactory<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
 #[cfg(test)] 
 mod tests{
     use super::*;
-    use maplit::hashset;
+    use crate::{
+        decode::DecodeOptions,
+        testing::{servers, Payload},
+    };
     use serde_json::json;
 
     #[test]
     fn encode_single_configuration_command(){
         //Arrange
-        let mut command = Command::SingleConfiguration{
-            old_configuration: hashset!(5, 42, 85, 13531, 8354),
-            configuration:  hashset!(42, 85, 13531, 8354),
+        let command = Command::SingleConfiguration{
+            old_configuration: servers!(5, 42, 85, 13531, 8354),
+            configuration:  servers!(42, 85, 13531, 8354),
+            learners: servers!(),
+            change_id: None,
         };
 
-        let entry = LogEntry {
+        let entry = LogEntry::<Payload> {
             term: 9,
             command: Some(command),
         };
 
         //Act
-        assert_eq(
+        let encoded = entry.to_json();
+
+        //Assert
+        assert_eq!(
             json!({
                 "type": "SingleConfiguration",
                 "term": 9,
                 "command": {
                     "configuration": {
-                        "instanceIds":  [5, 42, 85, 8354, 13531],
+                        "instanceIds": [42, 85, 8354, 13531],
                     },
                     "oldConfiguration": {
-                        "instanceIds": [42, 85, 8354, 13531],
+                        "instanceIds": [5, 42, 85, 8354, 13531],
                     },
                 }
-
             }),
-            entry.to_json()
+            encoded
         );
     }
 
     #[test]
     fn decode_single_configuration_command(){
         //Arrange
-        let encodedEntry = json!({
+        let encoded_entry = json!({
             "type": "SingleConfiguration",
             "term": 9,
             "command": {
@@ -296,65 +1211,71 @@ mod tests{
                 },
             }
         });
+
         //Act
         let LogEntry{
-            term, 
+            term,
             command
-        } = LogEntry::from(&encoded_entry);
-        assert_eq!(9, .term);
-        assert!(command.is_some());
+        } = LogEntry::<Payload>::try_from(&encoded_entry).unwrap();
+
+        //Assert
+        assert_eq!(9, term);
         let command = command.unwrap();
         assert_eq!("SingleConfiguration", command.command_type());
         match command {
-            Command::SingleConfiguration{old_configuration, configuration} => {
+            Command::SingleConfiguration{old_configuration, configuration, ..} => {
                 assert_eq!(
-                     hashset!(42, 85, 13531, 8354),
+                    servers!(42, 85, 13531, 8354),
                     configuration
                 );
                 assert_eq!(
-                    hashset!(5, 42, 85, 13531, 8354),
+                    servers!(5, 42, 85, 13531, 8354),
                     old_configuration
                 );
             },
-            _ => panic!("expected `Command::SingleConfiguration`");
+            _ => panic!("expected `Command::SingleConfiguration`"),
         }
     }
+
     #[test]
     fn encode_joint_configuration_command(){
         //Arrange
-        let mut command = Command::JointConfiguration{
-            old_configuration: hashset!(5, 42, 85, 13531, 8354),
-            new_configuration:  hashset!(42, 85, 13531, 8354),
+        let command = Command::JointConfiguration{
+            old_configuration: servers!(5, 42, 85, 13531, 8354),
+            new_configuration:  servers!(42, 85, 13531, 8354),
+            change_id: None,
         };
 
-        let entry = LogEntry {
+        let entry = LogEntry::<Payload> {
             term: 9,
             command: Some(command),
         };
 
         //Act
-        assert_eq(
+        let encoded = entry.to_json();
+
+        //Assert
+        assert_eq!(
             json!({
                 "type": "JointConfiguration",
                 "term": 9,
                 "command": {
-                    "configuration": {
-                        "instanceIds":  [5, 42, 85, 8354, 13531],
+                    "oldConfiguration": {
+                        "instanceIds": [5, 42, 85, 8354, 13531],
                     },
                     "newConfiguration": {
                         "instanceIds": [42, 85, 8354, 13531],
                     },
                 }
-
             }),
-            entry.to_json()
+            encoded
         );
     }
 
     #[test]
     fn decode_joint_configuration_command(){
         //Arrange
-        let encodedEntry = json!({
+        let encoded_entry = json!({
             "type": "JointConfiguration",
             "term": 9,
             "command": {
@@ -369,150 +1290,1618 @@ mod tests{
 
         //Act
         let LogEntry{
-            term, 
+            term,
             command
-        } = LogEntry::from(encoded_entry);
-        assert_eq!(9, .term);
-        assert!(command.is_some());
+        } = LogEntry::<Payload>::try_from(&encoded_entry).unwrap();
+
+        //Assert
+        assert_eq!(9, term);
         let command = command.unwrap();
         assert_eq!("JointConfiguration", command.command_type());
         match command {
-            Command::JointConfiguration{old_configuration, new_configuration} => {
+            Command::JointConfiguration{old_configuration, new_configuration, ..} => {
                 assert_eq!(
-                    hashset!(42, 85, 13531, 8354),
+                    servers!(42, 85, 13531, 8354),
                     new_configuration
                 );
                 assert_eq!(
-                    hashset!(5, 42, 85, 13531, 8354),
+                    servers!(5, 42, 85, 13531, 8354),
                     old_configuration
                 );
             },
-            _ => panic!("expected `Command::JointConfiguration`");
+            _ => panic!("expected `Command::JointConfiguration`"),
         }
     }
 
     #[test]
     fn to_json_without_command(){
         //Arrange
-        let entry = LogEntry{ term: 9, command: None};
+        let entry = LogEntry::<Payload>{ term: 9, command: None};
 
         //Act
-        assert!(
+        let encoded = entry.to_json();
+
+        //Assert
+        assert_eq!(
             json!({
-                {"term", 9}
+                "term": 9,
             }),
-            entry.to_json()
+            encoded
         );
     }
 
     #[test]
     fn from_json_without_command(){
+        //Arrange
         let entry_as_json = json!({
             "term": 9,
         });
-        let entry = LogEntry::from(entry_as_json);
-        assert_eq!(9, entry.term);
-        assert!(entry.command == is_none());
 
-         
+        //Act
+        let entry = LogEntry::<Payload>::try_from(&entry_as_json).unwrap();
+
+        //Assert
+        assert_eq!(9, entry.term);
+        assert!(entry.command.is_none());
     }
 
     #[test]
     fn compare_equal(){
+        //Arrange
         let examples = [
-                    json!({
-                        "type": "SingleConfiguration",
-                        "term": 9,
-                        "command": {
-                            "oldConfiguration": {
-                                "instanceIds": [5, 42, 85, 8354, 13531]
-                            },
-                            "configuration": {
-                                "instanceIds": [42, 85, 8354, 13531]
-                            },
-                        }
-                    }),
-                    json!({
-                        "type": "SingleConfiguration",
-                        "term": 8,
-                        "command": {
-                            "oldConfiguration": {
-                                "instanceIds": [5, 42, 85, 8354, 13531]
-                            },
-                            "configuration": {
-                                "instanceIds": [42, 85, 8354, 13531]
-                            },
-                        }
-                    }),
-                    json!({
-                        "type": "SingleConfiguration",
-                        "term": 9,
-                        "command": {
-                            "oldConfiguration": {
-                                "instanceIds": [5, 42, 85, 8354, 13531]
-                            },
-                            "configuration": {
-                                "instanceIds": [5, 85, 8354, 13531]
-                            },
-                        }
-                    }),
-                    json!({
-                        "term": 8,
-                    }),
-                    json!({
-                        "term": 9,
-                    })
+            json!({
+                "type": "SingleConfiguration",
+                "term": 9,
+                "command": {
+                    "oldConfiguration": {
+                        "instanceIds": [5, 42, 85, 8354, 13531]
+                    },
+                    "configuration": {
+                        "instanceIds": [42, 85, 8354, 13531]
+                    },
                 }
-        ]
-        .iter()
-        .map(LogEntry::from)
-        .collect::<Vec<_>>();
-        let num_examples = examples.len();
-        for i in 0..num_examples{
-            for j in 0..num_examples{
+            }),
+            json!({
+                "type": "SingleConfiguration",
+                "term": 8,
+                "command": {
+                    "oldConfiguration": {
+                        "instanceIds": [5, 42, 85, 8354, 13531]
+                    },
+                    "configuration": {
+                        "instanceIds": [42, 85, 8354, 13531]
+                    },
+                }
+            }),
+            json!({
+                "type": "SingleConfiguration",
+                "term": 9,
+                "command": {
+                    "oldConfiguration": {
+                        "instanceIds": [5, 42, 85, 8354, 13531]
+                    },
+                    "configuration": {
+                        "instanceIds": [5, 85, 8354, 13531]
+                    },
+                }
+            }),
+            json!({
+                "term": 8,
+            }),
+            json!({
+                "term": 9,
+            }),
+        ];
+
+        //Act
+        let examples = examples
+            .iter()
+            .map(|json| LogEntry::<Payload>::try_from(json).unwrap())
+            .collect::<Vec<_>>();
+
+        //Assert
+        for i in 0..examples.len(){
+            for j in 0..examples.len(){
                 if i == j{
-                    assert_eq(examples[i], examples[j]);
+                    assert_eq!(examples[i], examples[j]);
                 }else{
-                    assert_ne(examples[i], examples[j]);
+                    assert_ne!(examples[i], examples[j]);
                 }
             }
         }
+    }
+
+    #[test]
+    fn custom_command(){
+        //Arrange
+        #[derive(Debug, PartialEq)]
+        struct PogChamp {
+            payload: usize,
+        }
 
-        #[test]
-        fn custom_command(){
-            struct PogChamp {
-                payload: usize,
+        impl CustomCommand for PogChamp {
+            fn command_type(&self) -> &'static str {
+                "PogChamp"
             }
-    
-            impl CustomCommand for PogChamp {
-                fn command_type(&self) -> &'static str {
-                    "PogChamp"
-                }
 
-                fn encode(&self) -> JsonValue{
-                    json!({
-                        "payload": self.payload,
-                    })
-                }
+            fn to_json(&self) -> JsonValue{
+                json!({
+                    "payload": self.payload,
+                })
+            }
+
+            fn from_json(json: &JsonValue) -> Result<Self, CommandDecodeError> {
+                Ok(PogChamp{
+                    payload: json
+                        .get("command")
+                        .and_then(|command| command.get("payload"))
+                        .and_then(JsonValue::as_u64)
+                        .unwrap_or(0) as usize,
+                })
             }
+        }
 
-            let pog_champ = PogChamp{
+        let pog_champ_entry = LogEntry{
+            term: 8,
+            command: Some(Command::Custom(PogChamp{
                 payload: 42,
-            };
-            let pog_champ_entry;
-            pog_champ_entry.term = 8;
-            pog_champ_entry.command = pog_champ;
-            const json::Value serializedpog_champ = pog_champ_entry;
-            
-            let pog_champ_factory = |command_as_json: JsonValue| {
-                PogChamp{
-                    payload: command_as_json.get("payload")
-                        .map(JsonValue::as_u64)
-                        .and_then(|payload| payload as usize)
-                        .unwrap_or(0),
+            })),
+        };
+        let serialized_pog_champ = pog_champ_entry.to_json();
+
+        let pog_champ_factory = |command_as_json: &JsonValue| {
+            command_as_json
+                .get("payload")
+                .and_then(JsonValue::as_u64)
+                .map(|payload| PogChamp{
+                    payload: payload as usize,
+                })
+        };
+        let mut log_entry_factory = LogEntryFactory::new();
+        log_entry_factory.register("PogChamp", pog_champ_factory);
+
+        //Act
+        let decoded = log_entry_factory.decode(&serialized_pog_champ);
+
+        //Assert
+        assert_eq!(Some(pog_champ_entry), decoded.ok());
+    }
+
+    #[test]
+    fn single_server_change_adds_server(){
+        //Arrange
+        let current = servers!(1, 2, 3);
+
+        //Act
+        let command = Command::<()>::single_server_change(&current, Some(ServerId(4)), None);
+
+        //Assert
+        assert_eq!(
+            Ok(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 2, 3, 4),
+                learners: servers!(),
+                change_id: None,
+            }),
+            command
+        );
+    }
+
+    #[test]
+    fn single_server_change_removes_server(){
+        //Arrange
+        let current = servers!(1, 2, 3);
+
+        //Act
+        let command = Command::<()>::single_server_change(&current, None, Some(ServerId(2)));
+
+        //Assert
+        assert_eq!(
+            Ok(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 3),
+                learners: servers!(),
+                change_id: None,
+            }),
+            command
+        );
+        assert_eq!(
+            Err(ConfigError::NotMember(ServerId(7))),
+            Command::<()>::single_server_change(&current, None, Some(ServerId(7)))
+        );
+    }
+
+    #[test]
+    fn single_server_change_replaces_server(){
+        //Arrange
+        let current = servers!(1, 2, 3);
+
+        //Act
+        let replace = Command::<()>::single_server_change(&current, Some(ServerId(4)), Some(ServerId(1)));
+
+        //Assert
+        assert_eq!(
+            Ok(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(2, 3, 4),
+                learners: servers!(),
+                change_id: None,
+            }),
+            replace
+        );
+        assert_eq!(
+            Err(ConfigError::NoChange),
+            Command::<()>::single_server_change(&current, None, None)
+        );
+        assert_eq!(
+            Err(ConfigError::AlreadyMember(ServerId(2))),
+            Command::<()>::single_server_change(&current, Some(ServerId(2)), Some(ServerId(1)))
+        );
+    }
+
+    #[test]
+    fn compare_custom_commands(){
+        //Arrange
+        let command = Command::Custom(Payload(42));
+
+        //Act
+        let same = Command::Custom(Payload(42));
+        let different = Command::Custom(Payload(7));
+
+        //Assert
+        assert_eq!(command, same);
+        assert_ne!(command, different);
+    }
+
+    #[test]
+    fn compare_custom_command_with_configuration(){
+        //Arrange
+        let custom = Command::Custom(Payload(42));
+        let configuration = Command::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+
+        //Assert
+        assert_ne!(custom, configuration);
+        assert_ne!(configuration, custom);
+    }
+
+    #[test]
+    fn voters_count_single_configuration(){
+        let command = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+        assert_eq!(3, command.voters_count());
+    }
+
+    #[test]
+    fn voters_count_joint_configuration_with_overlap(){
+        let command = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(2, 3, 4),
+            change_id: None,
+        };
+        assert_eq!(4, command.voters_count());
+    }
+
+    #[test]
+    fn voters_count_joint_configuration_disjoint(){
+        let command = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(4, 5),
+            change_id: None,
+        };
+        assert_eq!(5, command.voters_count());
+    }
+
+    #[test]
+    fn from_owned_json(){
+        //Arrange
+        let encoded_entry = json!({
+            "type": "Payload",
+            "term": 3,
+            "command": {
+                "value": 7,
+            },
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::try_from(encoded_entry.clone()).unwrap();
+        let command = Command::<Payload>::try_from(encoded_entry);
+
+        //Assert
+        assert_eq!(3, entry.term);
+        assert_eq!(Some(Command::Custom(Payload(7))), entry.command);
+        assert_eq!(Some(Command::Custom(Payload(7))), command.ok());
+    }
+
+    #[test]
+    fn from_owned_json_moves_custom_payload(){
+        //Arrange
+        #[derive(Debug, PartialEq)]
+        struct Text(String);
+        impl CustomCommand for Text {
+            fn command_type(&self) -> &'static str {
+                "Text"
+            }
+
+            fn to_json(&self) -> JsonValue {
+                json!({"text": self.0})
+            }
+
+            fn from_json(_json: &JsonValue) -> Result<Self, CommandDecodeError> {
+                Err(CommandDecodeError::Custom("only decoded from owned JSON".into()))
+            }
+
+            fn from_json_owned(mut json: JsonValue) -> Result<Self, CommandDecodeError> {
+                match json["command"]["text"].take() {
+                    JsonValue::String(text) => Ok(Text(text)),
+                    _ => Err(CommandDecodeError::Custom("text is missing".into())),
                 }
-            };
-            let mut log_entry_factory = LogEntryFactory::new();
-            log_entry_factory.register("PogChamp", pog_champ_factory);
+            }
         }
-}
\ No newline at end of file
+        let encoded_entry = json!({
+            "type": "Text",
+            "term": 3,
+            "command": {
+                "text": "hello",
+            },
+        });
+
+        //Act
+        let borrowed = LogEntry::<Text>::try_from(&encoded_entry);
+        let owned = LogEntry::<Text>::try_from(encoded_entry);
+
+        //Assert
+        assert!(borrowed.is_err());
+        assert_eq!(Some(Command::Custom(Text(String::from("hello")))), owned.unwrap().command);
+    }
+
+    #[test]
+    fn remap_ids_relabels_configuration(){
+        //Arrange
+        let command = Command::<()>::JointConfiguration{
+            old_configuration: servers!(3, 1, 2),
+            new_configuration: servers!(2, 3, 4),
+            change_id: None,
+        };
+
+        //Act
+        let remapped = command.remap_ids(|instance_id| ServerId(instance_id.0 * 10));
+
+        //Assert
+        assert_eq!(
+            Command::JointConfiguration{
+                old_configuration: servers!(10, 20, 30),
+                new_configuration: servers!(20, 30, 40),
+                change_id: None,
+            },
+            remapped
+        );
+    }
+
+    #[test]
+    fn remap_ids_leaves_custom_command_unchanged(){
+        //Arrange
+        let command = Command::Custom(String::from("payload"));
+
+        //Act
+        let remapped = command.remap_ids(|instance_id| ServerId(instance_id.0 + 1));
+
+        //Assert
+        assert_eq!(command, remapped);
+    }
+
+    #[test]
+    fn construct_and_encode_custom_command(){
+        //Arrange
+        let entry = LogEntry {
+            term: 5,
+            command: Some(Command::custom(Payload(42))),
+        };
+
+        //Act
+        let encoded = entry.to_json();
+
+        //Assert
+        assert_eq!(
+            json!({
+                "type": "Payload",
+                "term": 5,
+                "command": {
+                    "value": 42,
+                },
+            }),
+            encoded
+        );
+    }
+
+    #[test]
+    fn factory_falls_back_for_unregistered_types(){
+        //Arrange
+        let mut plugin = LogEntryFactory::new();
+        plugin.register("Plugin", |command: &JsonValue| {
+            command.get("value").and_then(JsonValue::as_u64).map(|value| value as usize)
+        });
+        let mut base = LogEntryFactory::new();
+        base.register("Base", |_: &JsonValue| Some(0));
+        let factory = base.with_fallback(plugin);
+
+        //Act
+        let entry = factory.decode(&json!({
+            "type": "Plugin",
+            "term": 4,
+            "command": {
+                "value": 17,
+            },
+        }));
+
+        //Assert
+        assert_eq!(
+            Some(LogEntry{term: 4, command: Some(Command::Custom(17))}),
+            entry.ok()
+        );
+        assert!(matches!(
+            factory.decode(&json!({"type": "Unknown", "term": 4, "command": {}})),
+            Err(CommandDecodeError::UnknownType(command_type)) if command_type == "Unknown"
+        ));
+    }
+
+    #[test]
+    fn factory_prefers_own_registration_over_fallback(){
+        //Arrange
+        let mut fallback = LogEntryFactory::new();
+        fallback.register("Shared", |_: &JsonValue| Some("fallback"));
+        let mut factory = LogEntryFactory::new();
+        factory.register("Shared", |_: &JsonValue| Some("primary"));
+        let factory = factory.with_fallback(fallback);
+
+        //Act
+        let entry = factory.decode(&json!({"type": "Shared", "term": 1, "command": {}}));
+
+        //Assert
+        assert_eq!(Some(Command::Custom("primary")), entry.unwrap().command);
+    }
+
+    #[test]
+    fn factory_decodes_built_in_types_like_try_from(){
+        //Arrange
+        let mut factory = LogEntryFactory::new();
+        factory.register("Plugin", |command: &JsonValue| {
+            command.get("value").and_then(JsonValue::as_u64).map(|value| value as usize)
+        });
+
+        //Act
+        let batch = factory.decode(&json!({
+            "type": "Batch",
+            "term": 2,
+            "command": {
+                "entries": [
+                    {"type": "NoOp", "command": {}},
+                    {"type": "Plugin", "command": {"value": 3}},
+                ],
+            },
+        }));
+        let corrupt = factory.decode(&json!({"type": "ClientData", "term": 2, "command": {}}));
+        let failed = factory.decode(&json!({"type": "Plugin", "term": 2, "command": {}}));
+
+        //Assert
+        assert_eq!(
+            Some(Command::Batch(vec![Command::NoOp, Command::Custom(3)])),
+            batch.unwrap().command
+        );
+        assert!(matches!(corrupt, Err(CommandDecodeError::MalformedClientData)));
+        assert!(matches!(failed, Err(CommandDecodeError::Custom(_))));
+    }
+
+    #[test]
+    fn removes_server_detects_removed_id(){
+        let command = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            configuration: servers!(2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+        assert!(command.removes_server(ServerId(1)));
+        let command = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(2, 3, 4),
+            change_id: None,
+        };
+        assert!(command.removes_server(ServerId(1)));
+    }
+
+    #[test]
+    fn removes_server_ignores_retained_id(){
+        let command = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            configuration: servers!(2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+        assert!(!command.removes_server(ServerId(2)));
+        assert!(!command.removes_server(ServerId(7)));
+        assert!(!Command::Custom(()).removes_server(ServerId(1)));
+    }
+
+    #[test]
+    fn voter_change_magnitude_counts_added_and_removed(){
+        let previous = servers!(1, 2, 3);
+        let single = |configuration| Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            configuration,
+            learners: servers!(),
+            change_id: None,
+        };
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(4, 5, 6),
+            change_id: None,
+        };
+        assert_eq!(1, single(servers!(1, 2, 3, 4)).voter_change_magnitude(&previous));
+        assert_eq!(2, single(servers!(1, 2, 4)).voter_change_magnitude(&previous));
+        assert_eq!(0, single(servers!(1, 2, 3)).voter_change_magnitude(&previous));
+        assert_eq!(6, joint.voter_change_magnitude(&previous));
+        assert_eq!(0, Command::Custom(()).voter_change_magnitude(&previous));
+    }
+
+    #[test]
+    fn has_quorum_needs_a_strict_majority(){
+        let single = |configuration| Command::<()>::SingleConfiguration{
+            old_configuration: servers!(),
+            configuration,
+            learners: servers!(),
+            change_id: None,
+        };
+        assert!(!single(servers!()).has_quorum(&servers!()));
+        assert!(!single(servers!(1)).has_quorum(&servers!()));
+        assert!(single(servers!(1)).has_quorum(&servers!(1)));
+        assert!(!single(servers!(1, 2)).has_quorum(&servers!(1)));
+        assert!(single(servers!(1, 2)).has_quorum(&servers!(1, 2)));
+        assert!(!single(servers!(1, 2, 3)).has_quorum(&servers!(1, 4)));
+        assert!(single(servers!(1, 2, 3)).has_quorum(&servers!(1, 3)));
+        assert!(!single(servers!(1, 2, 3, 4)).has_quorum(&servers!(1, 2)));
+        assert!(single(servers!(1, 2, 3, 4)).has_quorum(&servers!(1, 2, 4)));
+        assert!(!single(servers!(1, 2, 3, 4, 5)).has_quorum(&servers!(1, 2, 6, 7)));
+        assert!(single(servers!(1, 2, 3, 4, 5)).has_quorum(&servers!(1, 2, 5)));
+    }
+
+    #[test]
+    fn has_quorum_of_joint_configuration_needs_both_majorities(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(3, 4, 5),
+            change_id: None,
+        };
+        assert!(!joint.has_quorum(&servers!(1, 2, 4)));
+        assert!(!joint.has_quorum(&servers!(2, 4, 5)));
+        assert!(joint.has_quorum(&servers!(2, 3, 4)));
+        assert!(!Command::Custom(()).has_quorum(&servers!(1)));
+    }
+
+    #[test]
+    fn canonicalize_serializes_independently_of_construction(){
+        //Arrange
+        let mut forward = HashSet::new();
+        let mut backward = HashSet::new();
+        for instance_id in 0..32 {
+            forward.insert(ServerId(instance_id));
+            backward.insert(ServerId(31 - instance_id));
+        }
+        forward.insert(ServerId(7));
+        let command = |configuration| Command::<Payload>::SingleConfiguration{
+            old_configuration: servers!(3, 1, 2),
+            configuration,
+            learners: servers!(),
+            change_id: Some(4),
+        };
+
+        //Act
+        let from_forward = command(forward).canonicalize();
+        let from_backward = command(backward).canonicalize();
+
+        //Assert
+        assert_eq!(from_forward.to_json().to_string(), from_backward.to_json().to_string());
+        assert_eq!(Some(4), from_forward.change_id());
+        assert_eq!(Command::Custom(Payload(5)), Command::Custom(Payload(5)).canonicalize());
+    }
+
+    #[test]
+    fn builder_builds_config_change_entry(){
+        //Act
+        let entry = LogEntry::<()>::builder()
+            .term(3)
+            .command(Command::revert_to(&servers!(1, 2)))
+            .build();
+
+        //Assert
+        assert_eq!(
+            LogEntry{
+                term: 3,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(),
+                    configuration: servers!(1, 2),
+                    learners: servers!(),
+                    change_id: None,
+                }),
+            },
+            entry
+        );
+    }
+
+    #[test]
+    fn builder_defaults_to_term_zero_without_command(){
+        let entry = LogEntry::<()>::builder().build();
+        let cleared = LogEntry::builder()
+            .term(2)
+            .command(Command::Custom(()))
+            .no_command()
+            .build();
+        assert_eq!(LogEntry{term: 0, command: None}, entry);
+        assert_eq!(LogEntry{term: 2, command: None}, cleared);
+    }
+
+    #[test]
+    fn debug_renders_sorted_instance_ids(){
+        let single = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(2, 1),
+            configuration: servers!(3, 1, 2),
+            learners: servers!(),
+            change_id: None,
+        };
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1),
+            new_configuration: servers!(5, 4),
+            change_id: None,
+        };
+        assert_eq!(
+            "SingleConfiguration([ServerId(1), ServerId(2)] -> [ServerId(1), ServerId(2), ServerId(3)])",
+            format!("{:?}", single)
+        );
+        assert_eq!(
+            "JointConfiguration([ServerId(1)] -> [ServerId(4), ServerId(5)])",
+            format!("{:?}", joint)
+        );
+    }
+
+    #[test]
+    fn departing_servers_of_each_variant(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(2, 3, 4),
+            change_id: None,
+        };
+        let removal = Command::<()>::remove_server(&servers!(1, 2, 3), ServerId(2)).unwrap();
+        let addition = Command::<()>::single_server_change(&servers!(1, 2, 3), Some(ServerId(4)), None).unwrap();
+        assert_eq!(Some(servers!(1)), joint.departing_servers());
+        assert_eq!(Some(servers!(2)), removal.departing_servers());
+        assert_eq!(Some(servers!()), addition.departing_servers());
+        assert_eq!(None, Command::Custom(()).departing_servers());
+    }
+
+    #[test]
+    fn server_ids_are_the_sorted_union(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(5, 1, 3),
+            new_configuration: servers!(3, 4, 5),
+            change_id: None,
+        };
+        let single = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(2),
+            configuration: servers!(2, 3),
+            learners: servers!(9),
+            change_id: None,
+        };
+        assert_eq!(
+            vec![ServerId(1), ServerId(3), ServerId(4), ServerId(5)],
+            joint.server_ids().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![ServerId(2), ServerId(3), ServerId(9)],
+            single.server_ids().collect::<Vec<_>>()
+        );
+        assert_eq!(0, Command::Custom(()).server_ids().count());
+        assert_eq!(0, Command::<()>::NoOp.server_ids().count());
+    }
+
+    #[test]
+    fn active_voters_of_each_variant(){
+        let single = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2),
+            new_configuration: servers!(3, 4),
+            change_id: None,
+        };
+        assert_eq!(Some(servers!(1, 2, 3)), single.active_voters());
+        assert_eq!(Some(servers!(1, 2, 3, 4)), joint.active_voters());
+        assert_eq!(None, Command::Custom(()).active_voters());
+    }
+
+    #[test]
+    fn membership_round_trip(){
+        //Arrange
+        let membership = [9, 3, 5, 3].map(ServerId);
+
+        //Act
+        let command = Command::<()>::from_membership(&membership);
+
+        //Assert
+        assert_eq!(
+            Command::SingleConfiguration{
+                old_configuration: HashSet::new(),
+                configuration: servers!(3, 5, 9),
+                learners: servers!(),
+                change_id: None,
+            },
+            command
+        );
+        assert_eq!(Some(vec![ServerId(3), ServerId(5), ServerId(9)]), command.to_membership());
+    }
+
+    #[test]
+    fn membership_of_joint_and_custom_commands(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(4, 1, 2),
+            new_configuration: servers!(2, 7),
+            change_id: None,
+        };
+        assert_eq!(Some([1, 2, 4, 7].map(ServerId).to_vec()), joint.to_membership());
+        assert_eq!(None, Command::Custom(()).to_membership());
+    }
+
+    #[test]
+    fn term_beyond_u32_round_trips(){
+        //Arrange
+        let entry = LogEntry::<Payload>{
+            term: u32::MAX as u64 + 7,
+            command: Some(Command::Custom(Payload(1))),
+        };
+
+        //Act
+        let json = entry.to_json();
+        let from_json = LogEntry::<Payload>::decode(&json, &DecodeOptions::default());
+        let from_bytes = LogEntry::<Payload>::from_bytes(&entry.to_bytes());
+
+        //Assert
+        assert_eq!(json!(4_294_967_302u64), json["term"]);
+        assert_eq!(Ok(&entry), from_json.as_ref());
+        assert_eq!(Ok(entry), from_bytes);
+    }
+
+    #[test]
+    fn change_id_round_trip(){
+        //Arrange
+        let entry = LogEntry::<Payload>{
+            term: 2,
+            command: Some(Command::JointConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                new_configuration: servers!(2, 3, 4),
+                change_id: Some(42),
+            }),
+        };
+
+        //Act
+        let encoded = entry.to_json();
+        let decoded = LogEntry::<Payload>::try_from(&encoded).unwrap();
+
+        //Assert
+        assert_eq!(json!(42), encoded["command"]["changeId"]);
+        assert_eq!(Some(42), decoded.command.as_ref().and_then(Command::change_id));
+    }
+
+    #[test]
+    fn change_id_is_ignored_by_equality(){
+        let tagged = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: Some(7),
+        };
+        let untagged = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(),
+            change_id: None,
+        };
+        assert_eq!(tagged, untagged);
+    }
+
+    #[test]
+    fn promote_moves_learner_into_voters(){
+        //Arrange
+        let command = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(4, 5),
+            change_id: None,
+        };
+
+        //Act
+        let promoted = command.promote(ServerId(4));
+
+        //Assert
+        assert_eq!(
+            Ok(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 2, 3, 4),
+                learners: servers!(5),
+                change_id: None,
+            }),
+            promoted
+        );
+    }
+
+    #[test]
+    fn promote_rejects_non_learner(){
+        //Arrange
+        let command = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1, 2),
+            configuration: servers!(1, 2, 3),
+            learners: servers!(4),
+            change_id: None,
+        };
+
+        //Act
+        let promoted = command.promote(ServerId(3));
+
+        //Assert
+        assert_eq!(Err(ConfigError::NotLearner(ServerId(3))), promoted);
+        assert_eq!(Err(ConfigError::NotLearner(ServerId(4))), Command::Custom(()).promote(ServerId(4)));
+    }
+
+    #[test]
+    fn learners_round_trip(){
+        //Arrange
+        let entry = LogEntry::<Payload>{
+            term: 3,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(1, 2, 3),
+                learners: servers!(5, 4),
+                change_id: None,
+            }),
+        };
+
+        //Act
+        let encoded = entry.to_json();
+
+        //Assert
+        assert_eq!(json!({"instanceIds": [4, 5]}), encoded["command"]["learners"]);
+        assert_eq!(entry, LogEntry::try_from(&encoded).unwrap());
+    }
+
+    #[test]
+    fn config_fingerprint_ignores_insertion_order(){
+        //Arrange
+        let mut forward = HashSet::new();
+        let mut backward = HashSet::new();
+        for instance_id in [85, 42, 13531].map(ServerId) {
+            forward.insert(instance_id);
+        }
+        for instance_id in [13531, 42, 85].map(ServerId) {
+            backward.insert(instance_id);
+        }
+
+        //Act
+        let forward = Command::<()>::from_membership(&sorted_instance_ids(&forward)).config_fingerprint();
+        let backward = Command::<()>::SingleConfiguration{
+            old_configuration: servers!(1),
+            configuration: backward,
+            learners: servers!(),
+            change_id: Some(3),
+        }.config_fingerprint();
+
+        //Assert
+        assert_eq!("single:[42,85,13531]", forward);
+        assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn config_fingerprint_distinguishes_single_and_joint(){
+        //Arrange
+        let single = Command::<()>::from_membership(&[ServerId(1), ServerId(2)]);
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(2, 1),
+            new_configuration: servers!(3, 2),
+            change_id: None,
+        };
+
+        //Act
+        let single = single.config_fingerprint();
+        let joint = joint.config_fingerprint();
+
+        //Assert
+        assert_eq!("single:[1,2]", single);
+        assert_eq!("joint:[1,2]->[2,3]", joint);
+        assert_ne!(single, joint);
+    }
+
+    #[test]
+    fn boxed_custom_commands_of_different_types_are_unequal(){
+        //Arrange
+        struct Renamed(usize);
+        impl CustomCommand for Renamed {
+            fn command_type(&self) -> &'static str {
+                "Renamed"
+            }
+
+            fn to_json(&self) -> JsonValue {
+                json!({
+                    "value": self.0,
+                })
+            }
+
+            fn from_json(json: &JsonValue) -> Result<Self, CommandDecodeError> {
+                Payload::from_json(json).map(|payload| Renamed(payload.0))
+            }
+        }
+        let payload = Command::<Box<dyn CustomCommand>>::Custom(Box::new(Payload(7)));
+        let same_payload = Command::<Box<dyn CustomCommand>>::Custom(Box::new(Payload(7)));
+        let renamed = Command::<Box<dyn CustomCommand>>::Custom(Box::new(Renamed(7)));
+
+        //Assert
+        assert!(payload == same_payload);
+        assert!(payload != renamed);
+    }
+
+    #[test]
+    fn audit_record_lists_added_server(){
+        //Arrange
+        let command = Command::<()>::single_server_change(&servers!(1, 2, 3), Some(ServerId(4)), None).unwrap();
+
+        //Act
+        let record = command.audit_record(12, 3, Some(ServerId(1)));
+
+        //Assert
+        assert_eq!(
+            Some(json!({
+                "index": 12,
+                "term": 3,
+                "change": "single:[1,2,3,4]",
+                "added": [4],
+                "removed": [],
+                "actor": 1,
+            })),
+            record
+        );
+        assert_eq!(None, Command::Custom(()).audit_record(12, 3, None));
+    }
+
+    #[test]
+    fn is_single_and_is_joint_match_variant(){
+        let single = Command::<()>::from_membership(&[ServerId(1), ServerId(2), ServerId(3)]);
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(2, 3, 4),
+            change_id: None,
+        };
+        let custom = Command::Custom(());
+
+        assert!(single.is_single());
+        assert!(!single.is_joint());
+        assert!(!joint.is_single());
+        assert!(joint.is_joint());
+        assert!(!custom.is_single());
+        assert!(!custom.is_joint());
+    }
+
+    #[cfg(feature = "serde-derive")]
+    #[test]
+    fn derived_serialization_matches_to_json(){
+        //Arrange
+        let entries = vec![
+            LogEntry::<Payload>{
+                term: 3,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(2, 1),
+                    configuration: servers!(3, 1, 2),
+                    learners: servers!(4),
+                    change_id: Some(9),
+                }),
+            },
+            LogEntry{
+                term: 4,
+                command: Some(Command::JointConfiguration{
+                    old_configuration: servers!(1, 2, 3),
+                    new_configuration: servers!(4, 3, 2),
+                    change_id: None,
+                }),
+            },
+        ];
+
+        for entry in entries {
+            //Act
+            let serialized = serde_json::to_value(&entry).unwrap();
+            let deserialized = serde_json::from_value::<LogEntry<Payload>>(serialized.clone()).unwrap();
+
+            //Assert
+            assert_eq!(entry.to_json(), serialized);
+            assert_eq!(entry, deserialized);
+        }
+    }
+
+    #[test]
+    fn decoding_malformed_json_does_not_panic(){
+        let inputs = [
+            JsonValue::Null,
+            json!([1, 2, 3]),
+            json!("SingleConfiguration"),
+            json!({"term": "x", "type": 7}),
+            json!({"term": -1, "type": "SingleConfiguration", "command": null}),
+            json!({"type": "SingleConfiguration", "command": {"configuration": {"instanceIds": ["a", -1, 1.5]}}}),
+            json!({"type": "JointConfiguration", "command": {"oldConfiguration": [1], "newConfiguration": 2}}),
+        ];
+        for input in &inputs {
+            if let Ok(entry) = LogEntry::<Payload>::try_from(input) {
+                assert!(entry.term == 0 || input["term"].is_u64());
+            }
+            let _ = Command::<Payload>::try_from(input);
+        }
+        assert!(matches!(
+            Command::<Payload>::try_from(&JsonValue::Null),
+            Err(CommandDecodeError::MissingType)
+        ));
+        assert!(matches!(
+            Command::<Payload>::try_from(&inputs[5]),
+            Err(CommandDecodeError::InvalidConfiguration(LogEntryError::InvalidInstanceId{pointer}))
+                if pointer == "/command/configuration/instanceIds/0"
+        ));
+    }
+
+    #[test]
+    fn try_from_reads_legacy_misspelled_key(){
+        let single = |configuration| json!({
+            "type": "SingleConfiguration",
+            "command": {"configuration": configuration},
+        });
+        let configuration = |json| match Command::<Payload>::try_from(&json) {
+            Ok(Command::SingleConfiguration{configuration, ..}) => configuration,
+            _ => panic!("expected `Command::SingleConfiguration`"),
+        };
+        assert_eq!(servers!(1, 2, 3), configuration(single(json!({"instanceIds": [3, 1, 2]}))));
+        assert_eq!(servers!(4, 5), configuration(single(json!({"instaceIds": [4, 5]}))));
+        assert_eq!(servers!(1), configuration(single(json!({"instanceIds": [1], "instaceIds": [4, 5]}))));
+    }
+
+    #[test]
+    fn try_from_rejects_joint_configuration_without_target(){
+        //Arrange
+        let json = json!({
+            "type": "JointConfiguration",
+            "term": 3,
+            "command": {"oldConfiguration": {"instanceIds": [1, 2, 3]}},
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::try_from(&json);
+
+        //Assert
+        assert!(matches!(
+            entry,
+            Err(CommandDecodeError::InvalidConfiguration(LogEntryError::MissingField("newConfiguration")))
+        ));
+    }
+
+    #[test]
+    fn with_old_configuration_sets_old_set_of_single(){
+        //Act
+        let command = Command::<Payload>::from_membership(&[ServerId(1), ServerId(2), ServerId(3)])
+            .with_old_configuration(servers!(2, 1));
+
+        //Assert
+        assert_eq!(
+            json!({
+                "configuration": {
+                    "instanceIds": [1, 2, 3]
+                },
+                "oldConfiguration": {
+                    "instanceIds": [1, 2]
+                },
+            }),
+            command.to_json()
+        );
+    }
+
+    #[test]
+    fn with_old_configuration_overrides_old_set_of_joint(){
+        //Arrange
+        let command = Command::<Payload>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(3, 4, 5),
+            change_id: Some(2),
+        };
+
+        //Act
+        let command = command.with_old_configuration(servers!(1, 3));
+
+        //Assert
+        assert_eq!(
+            json!({
+                "newConfiguration": {
+                    "instanceIds": [3, 4, 5]
+                },
+                "oldConfiguration": {
+                    "instanceIds": [1, 3]
+                },
+                "changeId": 2,
+            }),
+            command.to_json()
+        );
+        assert_eq!(
+            Command::Custom(Payload(1)),
+            Command::Custom(Payload(1)).with_old_configuration(servers!(1))
+        );
+    }
+
+    #[test]
+    fn try_from_reports_why_decoding_failed(){
+        assert!(matches!(
+            Command::<Payload>::try_from(json!({"term": 9})),
+            Err(CommandDecodeError::MissingType)
+        ));
+        assert!(matches!(
+            Command::<Payload>::try_from(json!({"type": 7})),
+            Err(CommandDecodeError::UnknownType(command_type)) if command_type == "7"
+        ));
+        assert!(matches!(
+            Command::<Payload>::try_from(json!({"type": "JointConfiguration", "command": [1, 2]})),
+            Err(CommandDecodeError::MalformedConfiguration)
+        ));
+    }
+
+    #[test]
+    fn try_from_surfaces_custom_command_errors(){
+        //Arrange
+        #[derive(Debug)]
+        struct Strict;
+        impl CustomCommand for Strict {
+            fn command_type(&self) -> &'static str {
+                "Strict"
+            }
+
+            fn to_json(&self) -> JsonValue {
+                json!({})
+            }
+
+            fn from_json(json: &JsonValue) -> Result<Self, CommandDecodeError> {
+                match json.get("command") {
+                    Some(JsonValue::Object(_)) => Ok(Strict),
+                    _ => Err(CommandDecodeError::Custom("Strict commands need a body".into())),
+                }
+            }
+        }
+
+        //Act
+        let error = Command::<Strict>::try_from(json!({"type": "Strict"})).unwrap_err();
+
+        //Assert
+        assert_eq!(
+            "custom command failed to decode: Strict commands need a body",
+            error.to_string()
+        );
+        assert!(Command::<Strict>::try_from(json!({"type": "Strict", "command": {}})).is_ok());
+    }
+
+    #[test]
+    fn try_from_rejects_corrupt_command(){
+        //Arrange
+        let corrupt = json!({
+            "type": "SingleConfiguration",
+            "term": 9,
+            "command": "oops",
+        });
+
+        //Act
+        let entry = LogEntry::<Payload>::try_from(&corrupt);
+
+        //Assert
+        assert!(matches!(entry, Err(CommandDecodeError::MalformedConfiguration)));
+    }
+
+    #[test]
+    fn try_from_accepts_entry_without_command(){
+        //Act
+        let entry = LogEntry::<Payload>::try_from(&json!({"term": 9}));
+
+        //Assert
+        assert_eq!(
+            Some(LogEntry {
+                term: 9,
+                command: None,
+            }),
+            entry.ok()
+        );
+    }
+
+    #[test]
+    fn leader_staying_in_new_config(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(1, 4, 5),
+            change_id: None,
+        };
+        assert!(joint.leader_in_new_config(ServerId(1)));
+        assert!(Command::<()>::from_membership(&[ServerId(1), ServerId(2)]).leader_in_new_config(ServerId(1)));
+    }
+
+    #[test]
+    fn leader_removed_by_new_config(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1, 2, 3),
+            new_configuration: servers!(2, 4, 5),
+            change_id: None,
+        };
+        assert!(!joint.leader_in_new_config(ServerId(1)));
+        assert!(!Command::<()>::from_membership(&[ServerId(2), ServerId(3)]).leader_in_new_config(ServerId(1)));
+        assert!(!Command::Custom(()).leader_in_new_config(ServerId(1)));
+    }
+
+    #[test]
+    fn batch_round_trip(){
+        //Arrange
+        let batch = Command::batch(vec![
+            Command::Custom(Payload(1)),
+            Command::Custom(Payload(2)),
+        ]).unwrap();
+
+        //Act
+        let json = LogEntry{term: 3, command: Some(batch)}.to_json();
+        let entry = LogEntry::<Payload>::try_from(&json);
+
+        //Assert
+        assert_eq!(
+            json!({
+                "term": 3,
+                "type": "Batch",
+                "command": {
+                    "entries": [
+                        {"type": "Payload", "command": {"value": 1}},
+                        {"type": "Payload", "command": {"value": 2}},
+                    ],
+                },
+            }),
+            json
+        );
+        assert_eq!(
+            Some(Command::Batch(vec![Command::Custom(Payload(1)), Command::Custom(Payload(2))])),
+            entry.unwrap().command
+        );
+    }
+
+    #[test]
+    fn batch_rejects_configuration_commands(){
+        //Arrange
+        let configuration = Command::<Payload>::from_membership(&[ServerId(1), ServerId(2), ServerId(3)]);
+        let json = json!({
+            "term": 3,
+            "type": "Batch",
+            "command": {
+                "entries": [
+                    {"type": "Payload", "command": {"value": 1}},
+                    {"type": configuration.command_type(), "command": configuration.to_json()},
+                ],
+            },
+        });
+
+        //Act
+        let built = Command::batch(vec![Command::Custom(Payload(1)), configuration]);
+        let decoded = LogEntry::<Payload>::try_from(&json);
+
+        //Assert
+        assert_eq!(Some(ConfigError::ConfigurationInBatch), built.err());
+        assert!(matches!(decoded, Err(CommandDecodeError::ConfigurationInBatch)));
+    }
+
+    #[test]
+    fn remove_server_drops_it_from_the_configuration(){
+        //Act
+        let command = Command::<()>::remove_server(&servers!(1, 2, 3), ServerId(1));
+
+        //Assert
+        assert_eq!(
+            Ok(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2, 3),
+                configuration: servers!(2, 3),
+                learners: servers!(),
+                change_id: None,
+            }),
+            command
+        );
+    }
+
+    #[test]
+    fn remove_server_rejects_emptying_the_cluster(){
+        assert_eq!(
+            Err(ConfigError::LastServer(ServerId(1))),
+            Command::<()>::remove_server(&servers!(1), ServerId(1))
+        );
+        assert_eq!(
+            Err(ConfigError::NotMember(ServerId(4))),
+            Command::<()>::remove_server(&servers!(1), ServerId(4))
+        );
+    }
+
+    #[test]
+    fn no_op_round_trip(){
+        //Arrange
+        let entry = LogEntry::<Payload>{
+            term: 4,
+            command: Some(Command::NoOp),
+        };
+
+        //Act
+        let json = entry.to_json();
+        let decoded = LogEntry::<Payload>::try_from(&json);
+
+        //Assert
+        assert_eq!(
+            json!({
+                "term": 4,
+                "type": "NoOp",
+                "command": {},
+            }),
+            json
+        );
+        assert_eq!(Some(entry), decoded.ok());
+    }
+
+    #[test]
+    fn no_op_entries_with_the_same_term_are_equal(){
+        //Arrange
+        let no_op = |term| LogEntry::<Payload>{
+            term,
+            command: Some(Command::NoOp),
+        };
+
+        //Assert
+        assert_eq!(no_op(4), no_op(4));
+        assert_ne!(no_op(4), no_op(5));
+        assert_ne!(Command::NoOp, Command::Custom(Payload(0)));
+    }
+
+    #[test]
+    fn client_data_round_trip(){
+        //Arrange
+        let command = Command::<Payload>::ClientData(b"hello".to_vec());
+
+        //Act
+        let json = json!({
+            "type": command.command_type(),
+            "command": command.to_json(),
+        });
+        let decoded = Command::<Payload>::try_from(&json);
+
+        //Assert
+        assert_eq!(
+            json!({
+                "type": "ClientData",
+                "command": {
+                    "data": "aGVsbG8=",
+                },
+            }),
+            json
+        );
+        assert_eq!(Some(command), decoded.ok());
+        assert_ne!(
+            Command::<Payload>::ClientData(vec![1]),
+            Command::<Payload>::ClientData(vec![2])
+        );
+    }
+
+    #[test]
+    fn client_data_must_be_base64(){
+        //Act
+        let decoded = Command::<Payload>::try_from(&json!({
+            "type": "ClientData",
+            "command": {
+                "data": "not base64",
+            },
+        }));
+
+        //Assert
+        assert!(matches!(decoded, Err(CommandDecodeError::MalformedClientData)));
+    }
+
+    #[test]
+    fn clone_preserves_every_variant(){
+        //Arrange
+        let entries = vec![
+            LogEntry{
+                term: 1,
+                command: None,
+            },
+            LogEntry{
+                term: 2,
+                command: Some(Command::SingleConfiguration{
+                    old_configuration: servers!(1, 2),
+                    configuration: servers!(1, 2, 3),
+                    learners: servers!(4),
+                    change_id: Some(6),
+                }),
+            },
+            LogEntry{
+                term: 3,
+                command: Some(Command::JointConfiguration{
+                    old_configuration: servers!(1, 2, 3),
+                    new_configuration: servers!(2, 3, 4),
+                    change_id: None,
+                }),
+            },
+            LogEntry{
+                term: 4,
+                command: Some(Command::Batch(vec![Command::Custom(String::from("a"))])),
+            },
+            LogEntry{
+                term: 5,
+                command: Some(Command::NoOp),
+            },
+            LogEntry{
+                term: 6,
+                command: Some(Command::ClientData(vec![1, 2, 3])),
+            },
+            LogEntry{
+                term: 7,
+                command: Some(Command::Custom(String::from("b"))),
+            },
+        ];
+
+        for entry in entries {
+            //Act
+            let clone = entry.clone();
+
+            //Assert
+            assert_eq!(entry, clone);
+            assert_eq!(
+                entry.command.as_ref().and_then(Command::change_id),
+                clone.command.as_ref().and_then(Command::change_id)
+            );
+        }
+    }
+
+    #[test]
+    fn single_view_borrows_voter_sets(){
+        //Arrange
+        let command = Command::<()>::single_server_change(&servers!(1, 2), Some(ServerId(3)), None).unwrap();
+
+        //Act
+        let view = command.as_single_view();
+
+        //Assert
+        assert_eq!(
+            Some(SingleConfigView{
+                voters: &servers!(1, 2, 3),
+                old_voters: &servers!(1, 2),
+            }),
+            view
+        );
+    }
+
+    #[test]
+    fn single_view_of_other_commands_is_none(){
+        let joint = Command::<()>::JointConfiguration{
+            old_configuration: servers!(1),
+            new_configuration: servers!(2),
+            change_id: None,
+        };
+        assert_eq!(None, Command::Custom(()).as_single_view());
+        assert_eq!(None, joint.as_single_view());
+    }
+
+    #[test]
+    fn equal_configurations_hash_identically(){
+        //Arrange
+        let hash = |entry: &LogEntry<()>| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            entry.hash(&mut hasher);
+            hasher.finish()
+        };
+        let mut forward = HashSet::new();
+        let mut backward = HashSet::new();
+        for instance_id in 0..64 {
+            forward.insert(ServerId(instance_id));
+            backward.insert(ServerId(63 - instance_id));
+        }
+        let entry = |configuration| LogEntry{
+            term: 2,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2),
+                configuration,
+                learners: servers!(),
+                change_id: None,
+            }),
+        };
+        let forward = entry(forward);
+        let backward = entry(backward);
+
+        //Assert
+        assert_eq!(forward, backward);
+        assert_eq!(hash(&forward), hash(&backward));
+        assert_eq!(1, [forward, backward].into_iter().collect::<HashSet<_>>().len());
+    }
+
+    #[test]
+    fn describe_summarises_each_variant(){
+        //Arrange
+        let add = Command::<Payload>::single_server_change(&servers!(1, 2, 3), Some(ServerId(7)), None).unwrap();
+        let remove = Command::<Payload>::remove_server(&servers!(1, 2, 3), ServerId(2)).unwrap();
+        let joint = Command::<Payload>::JointConfiguration{
+            old_configuration: servers!(3, 1, 2),
+            new_configuration: servers!(2, 3, 4),
+            change_id: None,
+        };
+
+        //Assert
+        assert_eq!("add server 7 (now 1,2,3,7)", add.describe());
+        assert_eq!("remove server 2 (now 1,3)", remove.describe());
+        assert_eq!("configuration 1,2", Command::<Payload>::from_membership(&[ServerId(1), ServerId(2)]).describe());
+        assert_eq!("joint 1,2,3 -> 2,3,4", joint.describe());
+        assert_eq!("no-op", Command::<Payload>::NoOp.describe());
+        assert_eq!("client data (3 bytes)", Command::<Payload>::ClientData(vec![1, 2, 3]).describe());
+        assert_eq!("client data (1 byte)", Command::<Payload>::ClientData(vec![1]).describe());
+        assert_eq!("batch of 1 command", Command::batch(vec![Command::Custom(Payload(1))]).unwrap().describe());
+        assert_eq!("batch of 2 commands", Command::batch(vec![Command::NoOp, Command::Custom(Payload(1))]).unwrap().describe());
+        assert_eq!("Payload", Command::Custom(Payload(1)).describe());
+    }
+
+    #[test]
+    fn entry_accessors(){
+        //Arrange
+        let configuration = LogEntry{
+            term: 3,
+            command: Some(Command::<Payload>::from_membership(&[ServerId(1)])),
+        };
+        let custom = LogEntry{
+            term: 4,
+            command: Some(Command::Custom(Payload(1))),
+        };
+
+        //Assert
+        assert_eq!(3, configuration.term());
+        assert!(configuration.is_config_change());
+        assert!(!custom.is_config_change());
+        assert!(!LogEntry::<Payload>{term: 1, command: None}.is_config_change());
+    }
+
+    #[test]
+    fn validate_rejects_commands_at_term_zero(){
+        //Arrange
+        let at_term_zero = LogEntry{
+            term: 0,
+            command: Some(Command::Custom(Payload(1))),
+        };
+
+        //Assert
+        assert_eq!(Err(LogEntryError::CommandAtTermZero), at_term_zero.validate());
+        assert_eq!(Ok(()), LogEntry::<Payload>{term: 0, command: None}.validate());
+        assert_eq!(Ok(()), LogEntry{term: 1, command: Some(Command::Custom(Payload(1)))}.validate());
+    }
+
+    #[test]
+    fn decoders_reject_commands_at_term_zero(){
+        //Arrange
+        let no_op = json!({"type": "NoOp", "term": 0, "command": {}});
+
+        //Act
+        let borrowed = LogEntry::<Payload>::try_from(&no_op);
+        let owned = LogEntry::<Payload>::try_from(no_op.clone());
+        let decoded = LogEntry::<Payload>::decode(&no_op, &DecodeOptions::default());
+        let from_factory = LogEntryFactory::<Payload>::new().decode(&no_op);
+
+        //Assert
+        assert!(matches!(borrowed, Err(CommandDecodeError::InvalidEntry(LogEntryError::CommandAtTermZero))));
+        assert!(matches!(owned, Err(CommandDecodeError::InvalidEntry(LogEntryError::CommandAtTermZero))));
+        assert_eq!(Err(LogEntryError::CommandAtTermZero), decoded);
+        assert!(matches!(from_factory, Err(CommandDecodeError::InvalidEntry(LogEntryError::CommandAtTermZero))));
+    }
+}
@@ -0,0 +1,284 @@
+// Reads the records file written by `FileLog`, each record a checksum
+// followed by a frame, through a memory map. Records are numbered by the
+// caller; `FileLog` numbers its entries from 1.
+
+use crate::{
+    file_log::{split_record, split_record_back},
+    framing::decode_payload,
+    log_entry::{CustomCommand, LogEntry},
+};
+use memmap2::Mmap;
+use std::{
+    fs::File,
+    io,
+    marker::PhantomData,
+    path::Path,
+};
+
+pub struct MmapLogReader {
+    map: Mmap,
+    // Holds the shared lock on the records file for as long as it is mapped.
+    _file: File,
+}
+
+impl MmapLogReader {
+    // Maps the records file of a `FileLog` at `path`. Fails with
+    // `WouldBlock` while a `FileLog` has the file open, and keeps any
+    // `FileLog` from opening it until the reader is dropped.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        file.try_lock_shared()?;
+        // Safety: the mapped bytes must not change while the map lives. The
+        // only writer is `FileLog`, which appends, truncates and on opening
+        // resizes the file, and it needs the exclusive lock on the file to
+        // do any of that, which it cannot take while we hold a shared one.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map, _file: file })
+    }
+
+    // Decodes records lazily, numbering them from `first_index`. A truncated
+    // record at the end of the file, such as one left by a torn write, ends
+    // the iteration; a record that fails its checksum is an error.
+    pub fn entries<T: CustomCommand>(&self, first_index: usize) -> MmapEntries<'_, T> {
+        MmapEntries {
+            remaining: &self.map,
+            next_index: first_index,
+            _command: PhantomData,
+        }
+    }
+
+    // Walks the records from the end of the file toward its start using each
+    // frame's trailing length, numbering them down from `last_index`. This
+    // finds recent entries, such as the latest configuration, without
+    // scanning the whole log. Unlike `entries`, a torn write at the end of
+    // the file is reported as an error, since there is no way to step over
+    // it backward.
+    pub fn entries_rev<T: CustomCommand>(&self, last_index: usize) -> MmapEntriesRev<'_, T> {
+        MmapEntriesRev {
+            remaining: &self.map,
+            next_index: last_index,
+            _command: PhantomData,
+        }
+    }
+}
+
+pub struct MmapEntries<'a, T> {
+    remaining: &'a [u8],
+    next_index: usize,
+    _command: PhantomData<T>,
+}
+
+impl<T: CustomCommand> Iterator for MmapEntries<'_, T> {
+    type Item = io::Result<(usize, LogEntry<T>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next_index;
+        let record = split_record(index, self.remaining)?;
+        match record.and_then(|(payload, rest)| Ok((decode_payload(payload)?, rest))) {
+            Ok((entry, rest)) => {
+                self.remaining = rest;
+                self.next_index += 1;
+                Some(Ok((index, entry)))
+            },
+            Err(error) => {
+                self.remaining = &[];
+                Some(Err(error))
+            },
+        }
+    }
+}
+
+pub struct MmapEntriesRev<'a, T> {
+    remaining: &'a [u8],
+    next_index: usize,
+    _command: PhantomData<T>,
+}
+
+impl<T: CustomCommand> Iterator for MmapEntriesRev<'_, T> {
+    type Item = io::Result<(usize, LogEntry<T>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next_index;
+        let record = split_record_back(index, self.remaining)?;
+        match record.and_then(|(rest, payload)| Ok((rest, decode_payload(payload)?))) {
+            Ok((rest, entry)) => {
+                self.remaining = rest;
+                self.next_index = self.next_index.saturating_sub(1);
+                Some(Ok((index, entry)))
+            },
+            Err(error) => {
+                self.remaining = &[];
+                Some(Err(error))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_log::FileLog,
+        log::LogError,
+        log_entry::Command,
+        storage::LogStorage,
+        testing::Payload,
+    };
+    use std::{
+        fs::{self, OpenOptions},
+        path::PathBuf,
+    };
+
+    fn entry(term: u64, value: usize) -> LogEntry<Payload> {
+        LogEntry {
+            term,
+            command: Some(Command::Custom(Payload(value))),
+        }
+    }
+
+    // Writes `entries` through a `FileLog` at a fresh path, closing it again
+    // so the file can be mapped.
+    fn write_log(name: &str, entries: &[LogEntry<Payload>]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("raft-mmap-{}-{}.log", name, std::process::id()));
+        let mut log = FileLog::<Payload>::open(&path).unwrap();
+        log.append(entries).unwrap();
+        path
+    }
+
+    fn remove(path: &Path) {
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(".idx");
+        fs::remove_file(path).unwrap();
+        fs::remove_file(index_path).unwrap();
+    }
+
+    fn tear_last_byte(path: &Path) {
+        let length = fs::metadata(path).unwrap().len();
+        OpenOptions::new().write(true).open(path).unwrap().set_len(length - 1).unwrap();
+    }
+
+    fn corrupt(result: &io::Result<(usize, LogEntry<Payload>)>) -> Option<&LogError> {
+        result
+            .as_ref()
+            .unwrap_err()
+            .get_ref()
+            .and_then(|error| error.downcast_ref::<LogError>())
+    }
+
+    #[test]
+    fn reads_back_file_log_with_truncated_tail() {
+        //Arrange
+        let path = write_log("tail", &[entry(1, 10), entry(1, 11), entry(2, 12), entry(3, 13)]);
+        tear_last_byte(&path);
+
+        //Act
+        let reader = MmapLogReader::open(&path).unwrap();
+        let entries = reader
+            .entries::<Payload>(1)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        drop(reader);
+        remove(&path);
+
+        //Assert
+        assert_eq!(
+            vec![
+                (1, entry(1, 10)),
+                (2, entry(1, 11)),
+                (3, entry(2, 12)),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn reads_records_backward() {
+        //Arrange
+        let path = write_log("rev", &[entry(1, 10), entry(1, 11), entry(2, 12), entry(3, 13)]);
+
+        //Act
+        let reader = MmapLogReader::open(&path).unwrap();
+        let mut forward = reader
+            .entries::<Payload>(1)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let backward = reader
+            .entries_rev::<Payload>(4)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        drop(reader);
+        remove(&path);
+
+        //Assert
+        assert_eq!(
+            vec![
+                (4, entry(3, 13)),
+                (3, entry(2, 12)),
+                (2, entry(1, 11)),
+                (1, entry(1, 10)),
+            ],
+            backward
+        );
+        forward.reverse();
+        assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn torn_tail_stops_backward_read() {
+        //Arrange
+        let path = write_log("torn", &[entry(1, 10), entry(2, 11)]);
+        tear_last_byte(&path);
+
+        //Act
+        let reader = MmapLogReader::open(&path).unwrap();
+        let entries = reader.entries_rev::<Payload>(2).collect::<Vec<_>>();
+        drop(reader);
+        remove(&path);
+
+        //Assert
+        assert_eq!(1, entries.len());
+        assert_eq!(io::ErrorKind::InvalidData, entries[0].as_ref().unwrap_err().kind());
+    }
+
+    #[test]
+    fn corrupt_record_fails_its_checksum_both_ways() {
+        //Arrange
+        let path = write_log("corrupt", &[entry(1, 10), entry(1, 11), entry(2, 12)]);
+        let mut bytes = fs::read(&path).unwrap();
+        let record_len = bytes.len() / 3;
+        bytes[record_len + 10] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+
+        //Act
+        let reader = MmapLogReader::open(&path).unwrap();
+        let forward = reader.entries::<Payload>(1).collect::<Vec<_>>();
+        let backward = reader.entries_rev::<Payload>(3).collect::<Vec<_>>();
+        drop(reader);
+        remove(&path);
+
+        //Assert
+        assert_eq!(2, forward.len());
+        assert_eq!(Some(&LogError::Corrupt{index: 2}), corrupt(&forward[1]));
+        assert_eq!(2, backward.len());
+        assert_eq!(Some(&LogError::Corrupt{index: 2}), corrupt(&backward[1]));
+    }
+
+    #[test]
+    fn file_log_and_reader_exclude_each_other() {
+        //Arrange
+        let path = write_log("exclusive", &[entry(1, 10)]);
+        let log = FileLog::<Payload>::open(&path).unwrap();
+
+        //Act
+        let while_logged = MmapLogReader::open(&path).map(|_| ());
+        drop(log);
+        let reader = MmapLogReader::open(&path).unwrap();
+        let while_mapped = FileLog::<Payload>::open(&path).map(|_| ());
+        drop(reader);
+        remove(&path);
+
+        //Assert
+        assert_eq!(io::ErrorKind::WouldBlock, while_logged.unwrap_err().kind());
+        assert_eq!(io::ErrorKind::WouldBlock, while_mapped.unwrap_err().kind());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    decode::LogEntryError,
+    log::Log,
+    log_entry::CustomCommand,
+    persistent_state::PersistentState,
+    server_id::ServerId,
+    snapshot::{decode_snapshot, encode_snapshot, SnapshotMeta},
+};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+
+// Everything a node keeps on disk, in one document for backup and restore:
+// its term and vote, its latest snapshot, and the log retained after it.
+#[derive(Debug, Eq, PartialEq)]
+pub struct NodeSnapshot<T> {
+    pub state: PersistentState,
+    pub meta: SnapshotMeta,
+    pub data: Vec<u8>,
+    pub log: Log<T>,
+}
+
+impl<T: CustomCommand> NodeSnapshot<T> {
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "currentTerm": self.state.current_term,
+            "votedFor": self.state.voted_for,
+            "snapshot": encode_snapshot(&self.meta, &self.data),
+            "log": self.log.to_json(),
+        })
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Self, LogEntryError> {
+        let current_term = json
+            .get("currentTerm")
+            .and_then(JsonValue::as_u64)
+            .ok_or(LogEntryError::MissingField("currentTerm"))?;
+        let voted_for = json
+            .get("votedFor")
+            .and_then(|voted_for| serde_json::from_value::<Option<ServerId>>(voted_for.clone()).ok())
+            .ok_or(LogEntryError::MissingField("votedFor"))?;
+        let (meta, data) = decode_snapshot(json.get("snapshot").ok_or(LogEntryError::MissingField("snapshot"))?)?;
+        let log = Log::from_json(json.get("log").ok_or(LogEntryError::MissingField("log"))?)?;
+        // The retained log must pick up exactly where the snapshot ends.
+        if (meta.last_included_index, meta.last_included_term) != (log.base_index(), log.base_term()) {
+            return Err(LogEntryError::SnapshotLogMismatch{
+                snapshot_index: meta.last_included_index,
+                snapshot_term: meta.last_included_term,
+                log_index: log.base_index(),
+                log_term: log.base_term(),
+            });
+        }
+        Ok(Self {
+            state: PersistentState {
+                current_term,
+                voted_for,
+            },
+            meta,
+            data,
+            log,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::{Command, LogEntry},
+        testing::{servers, Payload},
+    };
+
+    #[test]
+    fn round_trips_through_json() {
+        //Arrange
+        let mut log = Log::new();
+        for (term, value) in [(1, 10), (2, 11), (3, 12), (3, 13)] {
+            log.append(LogEntry {
+                term,
+                command: Some(Command::Custom(Payload(value))),
+            });
+        }
+        log.compact(2);
+        let node = NodeSnapshot {
+            state: PersistentState {
+                current_term: 4,
+                voted_for: Some(ServerId(2)),
+            },
+            meta: SnapshotMeta {
+                last_included_index: 2,
+                last_included_term: 2,
+                configuration: servers!(1, 2, 3),
+            },
+            data: vec![0, 1, 254, 255],
+            log,
+        };
+
+        //Act
+        let restored = NodeSnapshot::<Payload>::from_json(&node.to_json()).unwrap();
+
+        //Assert
+        assert_eq!(node, restored);
+        assert_eq!(4, restored.log.last_index());
+        assert_eq!(2, restored.log.base_index());
+    }
+
+    #[test]
+    fn snapshot_disagreeing_with_log_base_is_rejected() {
+        //Arrange
+        let node = |last_included_index, last_included_term| {
+            let mut log = Log::new();
+            for term in [1, 2, 3] {
+                log.append(LogEntry {
+                    term,
+                    command: Some(Command::Custom(Payload(10))),
+                });
+            }
+            log.compact(2);
+            NodeSnapshot {
+                state: PersistentState::default(),
+                meta: SnapshotMeta {
+                    last_included_index,
+                    last_included_term,
+                    configuration: servers!(1),
+                },
+                data: Vec::new(),
+                log,
+            }
+        };
+
+        //Act
+        let wrong_index = NodeSnapshot::<Payload>::from_json(&node(3, 2).to_json());
+        let wrong_term = NodeSnapshot::<Payload>::from_json(&node(2, 1).to_json());
+
+        //Assert
+        assert_eq!(
+            Err(LogEntryError::SnapshotLogMismatch{
+                snapshot_index: 3,
+                snapshot_term: 2,
+                log_index: 2,
+                log_term: 2,
+            }),
+            wrong_index
+        );
+        assert_eq!(
+            Err(LogEntryError::SnapshotLogMismatch{
+                snapshot_index: 2,
+                snapshot_term: 1,
+                log_index: 2,
+                log_term: 2,
+            }),
+            wrong_term
+        );
+    }
+
+    #[test]
+    fn missing_vote_is_distinct_from_no_vote() {
+        //Arrange
+        let mut json = NodeSnapshot::<Payload> {
+            state: PersistentState::default(),
+            meta: SnapshotMeta::default(),
+            data: Vec::new(),
+            log: Log::new(),
+        }
+        .to_json();
+
+        //Act
+        let restored = NodeSnapshot::<Payload>::from_json(&json).map(|node| node.state);
+        json.as_object_mut().unwrap().remove("votedFor");
+
+        //Assert
+        assert_eq!(Ok(PersistentState::default()), restored);
+        assert_eq!(
+            Err(LogEntryError::MissingField("votedFor")),
+            NodeSnapshot::<Payload>::from_json(&json)
+        );
+    }
+}
@@ -0,0 +1,8 @@
+use crate::server_id::ServerId;
+
+// What a server must persist before answering an RPC, besides its log.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PersistentState {
+    pub current_term: u64,
+    pub voted_for: Option<ServerId>,
+}
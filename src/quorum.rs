@@ -0,0 +1,59 @@
+use std::{
+    collections::HashSet,
+    hash::Hash,
+};
+
+pub fn majority(voters: usize) -> usize {
+    voters / 2 + 1
+}
+
+// Whether every majority of `old` shares at least one server with every
+// majority of `new`, so the two configurations can never elect different
+// leaders or commit different entries independently.
+//
+// A majority of `old` has to include at least `majority(|old|) - |old \ new|`
+// servers of the intersection, and likewise for `new`. Two disjoint
+// majorities exist exactly when both of those minimums fit in the
+// intersection side by side.
+pub fn quorums_overlap<T: Eq + Hash>(old: &HashSet<T>, new: &HashSet<T>) -> bool {
+    if old.is_empty() || new.is_empty() {
+        return false;
+    }
+    let shared = old.intersection(new).count();
+    let forced_from_old = majority(old.len()).saturating_sub(old.len() - shared);
+    let forced_from_new = majority(new.len()).saturating_sub(new.len() - shared);
+    forced_from_old + forced_from_new > shared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashset;
+
+    #[test]
+    fn single_server_changes_overlap() {
+        assert!(quorums_overlap(&hashset!(1, 2, 3), &hashset!(1, 2, 3, 4)));
+        assert!(quorums_overlap(&hashset!(1, 2, 3), &hashset!(1, 2)));
+        assert!(quorums_overlap(&hashset!(1, 2, 3, 4), &hashset!(1, 2, 3, 4, 5)));
+        assert!(quorums_overlap(&hashset!(1), &hashset!(1, 2)));
+    }
+
+    #[test]
+    fn complete_replacement_does_not_overlap() {
+        assert!(!quorums_overlap(&hashset!(1, 2, 3), &hashset!(4, 5, 6)));
+        assert!(!quorums_overlap(&hashset!(1), &hashset!(2)));
+    }
+
+    #[test]
+    fn swapping_one_server_overlaps_only_for_even_sizes() {
+        assert!(!quorums_overlap(&hashset!(1, 2, 3), &hashset!(1, 2, 4)));
+        assert!(quorums_overlap(&hashset!(1, 2), &hashset!(1, 3)));
+        assert!(quorums_overlap(&hashset!(1, 2, 3, 4), &hashset!(1, 2, 3, 5)));
+        assert!(!quorums_overlap(&hashset!(1, 2, 3, 4), &hashset!(1, 2, 5, 6)));
+    }
+
+    #[test]
+    fn empty_configuration_has_no_quorum() {
+        assert!(!quorums_overlap(&HashSet::new(), &hashset!(1)));
+    }
+}
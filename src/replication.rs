@@ -0,0 +1,218 @@
+use crate::{
+    append_entries::AppendEntriesRequest,
+    log::Log,
+    quorum::majority,
+    server_id::ServerId,
+    snapshot::SnapshotChunker,
+};
+use std::collections::{HashMap, HashSet};
+
+// A leader's view of how far each voter's log matches its own, and the
+// commit index that follows from it. The leader is expected to report its
+// own last index as its match index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplicationProgress {
+    match_index: HashMap<ServerId, usize>,
+    commit_index: usize,
+}
+
+impl ReplicationProgress {
+    pub fn new(voters: &HashSet<ServerId>, commit_index: usize) -> Self {
+        Self {
+            match_index: voters.iter().map(|&voter| (voter, 0)).collect(),
+            commit_index,
+        }
+    }
+
+    pub fn commit_index(&self) -> usize {
+        self.commit_index
+    }
+
+    pub fn match_index(&self, server: ServerId) -> Option<usize> {
+        self.match_index.get(&server).copied()
+    }
+
+    pub fn update_match<T>(&mut self, server: ServerId, match_index: usize, current_term: u64, log: &Log<T>) -> usize {
+        self.apply_match_updates(&[(server, match_index)], current_term, log)
+    }
+
+    // Records every `(server, match_index)` pair, then recomputes the commit
+    // index once. Match indices never move backward, so a stale response
+    // arriving late is ignored, as are responses from non-voters.
+    pub fn apply_match_updates<T>(
+        &mut self,
+        updates: &[(ServerId, usize)],
+        current_term: u64,
+        log: &Log<T>,
+    ) -> usize {
+        for &(server, match_index) in updates {
+            if let Some(known) = self.match_index.get_mut(&server) {
+                *known = (*known).max(match_index);
+            }
+        }
+        let mut match_indices = self.match_index.values().copied().collect::<Vec<_>>();
+        match_indices.sort_unstable_by(|a, b| b.cmp(a));
+        let replicated = match_indices
+            .get(majority(match_indices.len()) - 1)
+            .copied()
+            .unwrap_or(0);
+        // Only entries from the leader's own term are committed by counting
+        // replicas; earlier entries commit along with them.
+        if replicated > self.commit_index
+            && log.get(replicated).map(|entry| entry.term) == Some(current_term)
+        {
+            self.commit_index = replicated;
+        }
+        self.commit_index
+    }
+}
+
+// What a leader sends a follower next.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ReplicationAction<'a, T> {
+    AppendEntries(AppendEntriesRequest<T>),
+    InstallSnapshot(SnapshotChunker<'a>),
+}
+
+// Sends the entries after `follower_match` while they are still in the log,
+// and `snapshot` once the follower has fallen behind the compacted base.
+pub fn plan_replication<'a, T: Clone>(
+    follower_match: usize,
+    log: &Log<T>,
+    term: u64,
+    leader_id: ServerId,
+    leader_commit: usize,
+    snapshot: SnapshotChunker<'a>,
+) -> ReplicationAction<'a, T> {
+    let range = match log.catchup_range(follower_match) {
+        Some(range) => range,
+        None => return ReplicationAction::InstallSnapshot(snapshot),
+    };
+    let prev_log_index = range.start - 1;
+    ReplicationAction::AppendEntries(AppendEntriesRequest {
+        term,
+        leader_id,
+        prev_log_index,
+        prev_log_term: log.prev_term(range.start).unwrap_or(0),
+        entries: range.filter_map(|index| log.get(index).cloned()).collect(),
+        leader_commit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::LogEntry,
+        snapshot::SnapshotMeta,
+        testing::{servers, Payload},
+    };
+
+    fn log_with_terms(terms: &[u64]) -> Log<Payload> {
+        let mut log = Log::new();
+        for &term in terms {
+            log.append(LogEntry {
+                term,
+                command: None,
+            });
+        }
+        log
+    }
+
+    #[test]
+    fn batched_updates_match_individual_updates() {
+        //Arrange
+        let log = log_with_terms(&[1, 1, 2, 2, 2, 2]);
+        let voters = servers!(1, 2, 3, 4, 5);
+        let updates = [(1, 6), (2, 5), (3, 2), (4, 5), (2, 3), (9, 6)].map(|(server, match_index)| (ServerId(server), match_index));
+        let mut individually = ReplicationProgress::new(&voters, 0);
+        let mut batched = ReplicationProgress::new(&voters, 0);
+
+        //Act
+        for &(server, match_index) in &updates {
+            individually.update_match(server, match_index, 2, &log);
+        }
+        let commit_index = batched.apply_match_updates(&updates, 2, &log);
+
+        //Assert
+        assert_eq!(5, commit_index);
+        assert_eq!(individually, batched);
+        assert_eq!(Some(5), batched.match_index(ServerId(2)));
+        assert_eq!(None, batched.match_index(ServerId(9)));
+    }
+
+    #[test]
+    fn entries_from_earlier_terms_are_not_committed_by_counting() {
+        //Arrange
+        let log = log_with_terms(&[1, 1, 2]);
+        let mut progress = ReplicationProgress::new(&servers!(1, 2, 3), 0);
+
+        //Act
+        let commit_index = progress.apply_match_updates(&[(ServerId(1), 3), (ServerId(2), 2)], 2, &log);
+
+        //Assert
+        assert_eq!(0, commit_index);
+        assert_eq!(3, progress.apply_match_updates(&[(ServerId(2), 3)], 2, &log));
+    }
+
+    #[test]
+    fn follower_within_the_log_gets_entries() {
+        //Arrange
+        let mut log = log_with_terms(&[1, 1, 2, 2, 3]);
+        log.compact(2);
+        let meta = SnapshotMeta::default();
+
+        //Act
+        let action = plan_replication(3, &log, 3, ServerId(1), 4, SnapshotChunker::new(&meta, b"", 16));
+
+        //Assert
+        assert_eq!(
+            ReplicationAction::AppendEntries(AppendEntriesRequest {
+                term: 3,
+                leader_id: ServerId(1),
+                prev_log_index: 3,
+                prev_log_term: 2,
+                entries: vec![
+                    LogEntry {
+                        term: 2,
+                        command: None,
+                    },
+                    LogEntry {
+                        term: 3,
+                        command: None,
+                    },
+                ],
+                leader_commit: 4,
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn follower_behind_the_snapshot_gets_the_snapshot() {
+        //Arrange
+        let mut log = log_with_terms(&[1, 1, 2, 2, 3]);
+        log.compact(2);
+        let meta = SnapshotMeta {
+            last_included_index: 2,
+            last_included_term: 1,
+            configuration: servers!(1, 2, 3),
+        };
+
+        //Act
+        let action = plan_replication(1, &log, 3, ServerId(1), 4, SnapshotChunker::new(&meta, b"state", 3));
+
+        //Assert
+        match action {
+            ReplicationAction::InstallSnapshot(chunker) => {
+                assert_eq!(&meta, chunker.meta());
+                assert_eq!(2, chunker.count());
+            },
+            other => panic!("expected a snapshot, got {:?}", other),
+        }
+        assert!(matches!(
+            plan_replication(2, &log, 3, ServerId(1), 4, SnapshotChunker::new(&meta, b"", 3)),
+            ReplicationAction::AppendEntries(AppendEntriesRequest{prev_log_index: 2, prev_log_term: 1, ..})
+        ));
+    }
+}
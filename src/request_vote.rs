@@ -0,0 +1,265 @@
+use crate::{
+    decode::LogEntryError,
+    log::Log,
+    persistent_state::PersistentState,
+    server_id::ServerId,
+};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RequestVoteRequest {
+    pub term: u64,
+    pub candidate_id: ServerId,
+    pub last_log_index: usize,
+    pub last_log_term: u64,
+}
+
+// Why a vote was denied, sent back so a candidate can tell a split vote
+// from a log that is behind.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VoteDenialReason {
+    TermTooLow,
+    AlreadyVoted,
+    LogNotUpToDate,
+}
+
+impl VoteDenialReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VoteDenialReason::TermTooLow => "termTooLow",
+            VoteDenialReason::AlreadyVoted => "alreadyVoted",
+            VoteDenialReason::LogNotUpToDate => "logNotUpToDate",
+        }
+    }
+
+    fn from_str(reason: &str) -> Option<Self> {
+        [
+            VoteDenialReason::TermTooLow,
+            VoteDenialReason::AlreadyVoted,
+            VoteDenialReason::LogNotUpToDate,
+        ]
+        .into_iter()
+        .find(|known| known.as_str() == reason)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RequestVoteResponse {
+    pub term: u64,
+    pub vote_granted: bool,
+    // Set only when the vote is denied.
+    pub reason: Option<VoteDenialReason>,
+}
+
+impl RequestVoteResponse {
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = json!({
+            "term": self.term,
+            "voteGranted": self.vote_granted,
+        });
+        if let Some(reason) = self.reason {
+            json["reason"] = JsonValue::from(reason.as_str());
+        }
+        json
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Self, LogEntryError> {
+        let reason = match json.get("reason") {
+            None | Some(JsonValue::Null) => None,
+            Some(reason) => Some(
+                reason
+                    .as_str()
+                    .and_then(VoteDenialReason::from_str)
+                    .ok_or_else(|| LogEntryError::InvalidValue{
+                        field: "reason",
+                        value: reason.to_string(),
+                    })?
+            ),
+        };
+        Ok(Self {
+            term: json
+                .get("term")
+                .and_then(JsonValue::as_u64)
+                .ok_or(LogEntryError::MissingField("term"))?,
+            vote_granted: json
+                .get("voteGranted")
+                .and_then(JsonValue::as_bool)
+                .ok_or(LogEntryError::MissingField("voteGranted"))?,
+            reason,
+        })
+    }
+}
+
+// Decides a vote (Raft §5.2, §5.4.1), moving `state` to the candidate's
+// term if it is newer and recording the vote if granted. The caller must
+// persist `state` before sending the response.
+pub fn handle_request_vote<T>(
+    state: &mut PersistentState,
+    log: &Log<T>,
+    request: &RequestVoteRequest,
+) -> RequestVoteResponse {
+    if request.term > state.current_term {
+        state.current_term = request.term;
+        state.voted_for = None;
+    }
+    let reason = if request.term < state.current_term {
+        Some(VoteDenialReason::TermTooLow)
+    } else if state.voted_for.is_some_and(|voted_for| voted_for != request.candidate_id) {
+        Some(VoteDenialReason::AlreadyVoted)
+    } else if compare_up_to_date(request.last_log_term, request.last_log_index, log.last_term(), log.last_index())
+        == Ordering::Less
+    {
+        Some(VoteDenialReason::LogNotUpToDate)
+    } else {
+        state.voted_for = Some(request.candidate_id);
+        None
+    };
+    RequestVoteResponse {
+        term: state.current_term,
+        vote_granted: reason.is_none(),
+        reason,
+    }
+}
+
+// Compares a candidate's log with ours by their last entries (Raft §5.4.1):
+// a later last term wins, and with equal last terms the longer log wins.
+// `Equal` means the logs are equally up-to-date, so the vote may be granted
+// if it hasn't already been given to someone else this term.
+pub fn compare_up_to_date(
+    candidate_last_term: u64,
+    candidate_last_index: usize,
+    last_term: u64,
+    last_index: usize,
+) -> Ordering {
+    candidate_last_term
+        .cmp(&last_term)
+        .then(candidate_last_index.cmp(&last_index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::LogEntry,
+        testing::Payload,
+    };
+
+    #[test]
+    fn candidate_with_later_term_is_more_up_to_date() {
+        assert_eq!(Ordering::Greater, compare_up_to_date(3, 2, 2, 10));
+        assert_eq!(Ordering::Greater, compare_up_to_date(3, 6, 3, 5));
+    }
+
+    #[test]
+    fn candidate_with_earlier_term_or_shorter_log_is_behind() {
+        assert_eq!(Ordering::Less, compare_up_to_date(1, 10, 2, 1));
+        assert_eq!(Ordering::Less, compare_up_to_date(2, 4, 2, 5));
+    }
+
+    #[test]
+    fn identical_last_entries_are_equal() {
+        assert_eq!(Ordering::Equal, compare_up_to_date(2, 5, 2, 5));
+        assert_eq!(Ordering::Equal, compare_up_to_date(0, 0, 0, 0));
+    }
+
+    fn log_with_terms(terms: &[u64]) -> Log<Payload> {
+        let mut log = Log::new();
+        for &term in terms {
+            log.append(LogEntry {
+                term,
+                command: None,
+            });
+        }
+        log
+    }
+
+    fn request(term: u64, candidate_id: u64, last_log_index: usize, last_log_term: u64) -> RequestVoteRequest {
+        RequestVoteRequest {
+            term,
+            candidate_id: ServerId(candidate_id),
+            last_log_index,
+            last_log_term,
+        }
+    }
+
+    #[test]
+    fn granted_vote_carries_no_reason() {
+        //Arrange
+        let log = log_with_terms(&[1, 2]);
+        let mut state = PersistentState {
+            current_term: 2,
+            voted_for: None,
+        };
+
+        //Act
+        let response = handle_request_vote(&mut state, &log, &request(3, 4, 2, 2));
+
+        //Assert
+        assert_eq!(
+            RequestVoteResponse {
+                term: 3,
+                vote_granted: true,
+                reason: None,
+            },
+            response
+        );
+        assert_eq!(Some(ServerId(4)), state.voted_for);
+        assert_eq!(json!({"term": 3, "voteGranted": true}), response.to_json());
+    }
+
+    #[test]
+    fn each_denial_reports_its_reason() {
+        //Arrange
+        let log = log_with_terms(&[1, 2]);
+        let mut state = PersistentState {
+            current_term: 3,
+            voted_for: Some(ServerId(4)),
+        };
+
+        //Act
+        let stale = handle_request_vote(&mut state, &log, &request(2, 5, 2, 2));
+        let second_candidate = handle_request_vote(&mut state, &log, &request(3, 5, 2, 2));
+        let behind = handle_request_vote(&mut state, &log, &request(4, 5, 5, 1));
+
+        //Assert
+        assert_eq!(Some(VoteDenialReason::TermTooLow), stale.reason);
+        assert_eq!(Some(VoteDenialReason::AlreadyVoted), second_candidate.reason);
+        assert_eq!(Some(VoteDenialReason::LogNotUpToDate), behind.reason);
+        assert!(!behind.vote_granted);
+        assert_eq!(
+            PersistentState {
+                current_term: 4,
+                voted_for: None,
+            },
+            state
+        );
+    }
+
+    #[test]
+    fn response_round_trips_through_json() {
+        //Arrange
+        let response = RequestVoteResponse {
+            term: 7,
+            vote_granted: false,
+            reason: Some(VoteDenialReason::LogNotUpToDate),
+        };
+
+        //Act
+        let json = response.to_json();
+
+        //Assert
+        assert_eq!(json!("logNotUpToDate"), json["reason"]);
+        assert_eq!(Ok(response), RequestVoteResponse::from_json(&json));
+        assert_eq!(
+            Err(LogEntryError::InvalidValue{
+                field: "reason",
+                value: String::from("\"bored\""),
+            }),
+            RequestVoteResponse::from_json(&json!({"term": 7, "voteGranted": false, "reason": "bored"}))
+        );
+    }
+}
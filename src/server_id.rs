@@ -0,0 +1,52 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// Identifies a member of the cluster. On the wire it is a plain integer, as
+// in `{"instanceIds": [1, 2, 3]}`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ServerId(pub u64);
+
+impl From<u64> for ServerId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for ServerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for ServerId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ServerId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_as_a_plain_integer() {
+        //Arrange
+        let ids = vec![ServerId(1), ServerId::from(42)];
+
+        //Act
+        let json = serde_json::to_value(&ids).unwrap();
+        let decoded = serde_json::from_value::<Vec<ServerId>>(json.clone()).unwrap();
+
+        //Assert
+        assert_eq!(json!([1, 42]), json);
+        assert_eq!(ids, decoded);
+        assert_eq!("42", ServerId(42).to_string());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::log_entry::{CustomCommand, LogEntry};
+use serde_json::Value as JsonValue;
+
+// The `(term, command kind)` outline of an entry, for learning the shape of
+// a log before fetching command bodies.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogEntrySkeleton {
+    pub term: u64,
+    pub command_type: Option<String>,
+}
+
+impl LogEntrySkeleton {
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = serde_json::Map::new();
+        json.insert(String::from("term"), JsonValue::from(self.term));
+        if let Some(command_type) = &self.command_type {
+            json.insert(String::from("type"), JsonValue::from(command_type.as_str()));
+        }
+        JsonValue::Object(json)
+    }
+}
+
+impl From<&JsonValue> for LogEntrySkeleton {
+    fn from(json: &JsonValue) -> Self {
+        Self {
+            term: json
+                .get("term")
+                .and_then(JsonValue::as_u64)
+                .unwrap_or(0),
+            command_type: json
+                .get("type")
+                .and_then(JsonValue::as_str)
+                .map(String::from),
+        }
+    }
+}
+
+impl<T: CustomCommand> LogEntry<T> {
+    pub fn skeleton(&self) -> LogEntrySkeleton {
+        LogEntrySkeleton {
+            term: self.term,
+            command_type: self
+                .command
+                .as_ref()
+                .map(|command| String::from(command.command_type())),
+        }
+    }
+
+    pub fn to_json_skeleton(&self) -> JsonValue {
+        self.skeleton().to_json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::Command,
+        testing::{servers, Payload},
+    };
+    use serde_json::json;
+
+    #[test]
+    fn skeleton_round_trips_term_and_kind_without_body() {
+        //Arrange
+        let entry = LogEntry::<Payload> {
+            term: 7,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2),
+                configuration: servers!(1, 2, 3),
+                learners: servers!(),
+                change_id: None,
+            }),
+        };
+
+        //Act
+        let encoded = entry.to_json_skeleton();
+        let decoded = LogEntrySkeleton::from(&encoded);
+
+        //Assert
+        assert_eq!(
+            json!({
+                "term": 7,
+                "type": "SingleConfiguration",
+            }),
+            encoded
+        );
+        assert_eq!(
+            LogEntrySkeleton {
+                term: 7,
+                command_type: Some(String::from("SingleConfiguration")),
+            },
+            decoded
+        );
+    }
+
+    #[test]
+    fn skeleton_of_entry_without_command() {
+        //Arrange
+        let entry = LogEntry::<Payload> {
+            term: 2,
+            command: None,
+        };
+
+        //Act
+        let decoded = LogEntrySkeleton::from(&entry.to_json_skeleton());
+
+        //Assert
+        assert_eq!(json!({"term": 2}), entry.to_json_skeleton());
+        assert_eq!(None, decoded.command_type);
+        assert_eq!(2, decoded.term);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    base64,
+    decode::LogEntryError,
+    server_id::ServerId,
+};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SnapshotMeta {
+    pub last_included_index: usize,
+    pub last_included_term: u64,
+    pub configuration: HashSet<ServerId>,
+}
+
+// When `maintain` should take a snapshot: once the applied entries not yet
+// compacted number more than `max_entries`, or their encoding takes more
+// than `max_bytes`. An unset threshold never triggers.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SnapshotPolicy {
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+// Splits snapshot data into the chunks of an InstallSnapshot exchange. An
+// empty snapshot is still sent as one, empty, final chunk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SnapshotChunker<'a> {
+    meta: &'a SnapshotMeta,
+    data: &'a [u8],
+    chunk_size: usize,
+    offset: Option<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct SnapshotChunk<'a> {
+    pub offset: usize,
+    pub data: &'a [u8],
+    pub done: bool,
+}
+
+impl<'a> SnapshotChunker<'a> {
+    pub fn new(meta: &'a SnapshotMeta, data: &'a [u8], chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "snapshot chunks must hold at least one byte");
+        Self {
+            meta,
+            data,
+            chunk_size,
+            offset: Some(0),
+        }
+    }
+
+    pub fn meta(&self) -> &'a SnapshotMeta {
+        self.meta
+    }
+}
+
+impl<'a> Iterator for SnapshotChunker<'a> {
+    type Item = SnapshotChunk<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset?;
+        let end = (offset + self.chunk_size).min(self.data.len());
+        let done = end == self.data.len();
+        self.offset = if done { None } else { Some(end) };
+        Some(SnapshotChunk {
+            offset,
+            data: &self.data[offset..end],
+            done,
+        })
+    }
+}
+
+// A snapshot as stored alongside the log: its metadata, the configuration
+// as an array of ids, and the state machine's bytes as base64 `data`.
+pub(crate) fn encode_snapshot(meta: &SnapshotMeta, data: &[u8]) -> JsonValue {
+    let mut configuration = meta.configuration.iter().copied().collect::<Vec<_>>();
+    configuration.sort_unstable();
+    json!({
+        "lastIncludedIndex": meta.last_included_index,
+        "lastIncludedTerm": meta.last_included_term,
+        "configuration": configuration,
+        "data": base64::encode(data),
+    })
+}
+
+pub(crate) fn decode_snapshot(json: &JsonValue) -> Result<(SnapshotMeta, Vec<u8>), LogEntryError> {
+    let field = |field: &'static str| {
+        json.get(field)
+            .and_then(JsonValue::as_u64)
+            .ok_or(LogEntryError::MissingField(field))
+    };
+    let meta = SnapshotMeta {
+        last_included_index: field("lastIncludedIndex")? as usize,
+        last_included_term: field("lastIncludedTerm")?,
+        configuration: json
+            .get("configuration")
+            .and_then(|configuration| serde_json::from_value(configuration.clone()).ok())
+            .ok_or(LogEntryError::MissingField("configuration"))?,
+    };
+    let data = json
+        .get("data")
+        .and_then(JsonValue::as_str)
+        .and_then(base64::decode)
+        .ok_or(LogEntryError::MissingField("data"))?;
+    Ok((meta, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunker_covers_the_data_and_marks_the_last_chunk() {
+        //Arrange
+        let meta = SnapshotMeta::default();
+
+        //Act
+        let chunks = SnapshotChunker::new(&meta, b"abcde", 2).collect::<Vec<_>>();
+        let empty = SnapshotChunker::new(&meta, b"", 2).collect::<Vec<_>>();
+
+        //Assert
+        assert_eq!(
+            vec![
+                SnapshotChunk{offset: 0, data: &b"ab"[..], done: false},
+                SnapshotChunk{offset: 2, data: &b"cd"[..], done: false},
+                SnapshotChunk{offset: 4, data: &b"e"[..], done: true},
+            ],
+            chunks
+        );
+        assert_eq!(vec![SnapshotChunk{offset: 0, data: &b""[..], done: true}], empty);
+    }
+}
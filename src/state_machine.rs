@@ -0,0 +1,229 @@
+use crate::{
+    log::Log,
+    log_entry::{CustomCommand, LogEntry},
+    snapshot::{SnapshotMeta, SnapshotPolicy},
+};
+
+pub trait StateMachine<T> {
+    // Handed back to the client that submitted the applied command.
+    type Response;
+
+    fn apply(&mut self, index: usize, entry: &LogEntry<T>) -> Self::Response;
+}
+
+pub trait Snapshottable {
+    fn snapshot(&self) -> Vec<u8>;
+}
+
+// Applies the committed prefix of `log` to two state machines built by
+// `make_state_machine` and panics unless both end up with identical
+// snapshots, which catches `apply` implementations that are not
+// deterministic.
+pub fn verify_determinism<T, SM>(log: &Log<T>, commit_index: usize, make_state_machine: impl Fn() -> SM)
+where
+    SM: StateMachine<T> + Snapshottable,
+{
+    let replay = || {
+        let mut state_machine = make_state_machine();
+        for (index, entry) in log.iter_committed(commit_index) {
+            state_machine.apply(index, entry);
+        }
+        state_machine.snapshot()
+    };
+    let first = replay();
+    let second = replay();
+    assert_eq!(
+        first,
+        second,
+        "state machine diverged after replaying entries up to {}",
+        commit_index
+    );
+}
+
+// Applies entries up to `commit_index`, then snapshots the state machine
+// and compacts the log through the last applied entry if `policy` says the
+// uncompacted prefix has grown too large. Returns the snapshot, if one was
+// taken, for the caller to persist, with its metadata read from the log
+// before the compaction drops the entries it depends on.
+pub fn maintain<T, SM>(
+    log: &mut Log<T>,
+    state_machine: &mut SM,
+    commit_index: usize,
+    policy: &SnapshotPolicy,
+) -> Option<(SnapshotMeta, Vec<u8>)>
+where
+    T: CustomCommand,
+    SM: StateMachine<T> + Snapshottable,
+{
+    log.apply_committed(state_machine, commit_index);
+    let last_applied = log.last_applied();
+    let too_many_entries = policy
+        .max_entries
+        .is_some_and(|max_entries| last_applied.saturating_sub(log.base_index()) > max_entries);
+    let too_many_bytes = policy.max_bytes.is_some_and(|max_bytes| {
+        log.iter_committed(last_applied)
+            .map(|(_, entry)| entry.size_bytes())
+            .sum::<usize>()
+            > max_bytes
+    });
+    if !too_many_entries && !too_many_bytes {
+        return None;
+    }
+    let meta = SnapshotMeta {
+        last_included_index: last_applied,
+        last_included_term: log.prev_term(last_applied + 1).unwrap_or(0),
+        configuration: log.committed_configuration(last_applied, last_applied).unwrap_or_default(),
+    };
+    let snapshot = state_machine.snapshot();
+    log.compact(last_applied);
+    Some((meta, snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::Command,
+        testing::{servers, Payload},
+    };
+
+    #[derive(Default)]
+    struct Counter {
+        total: usize,
+    }
+
+    impl StateMachine<Payload> for Counter {
+        type Response = usize;
+
+        fn apply(&mut self, _index: usize, entry: &LogEntry<Payload>) -> usize {
+            if let Some(Command::Custom(Payload(amount))) = &entry.command {
+                self.total += amount;
+            }
+            self.total
+        }
+    }
+
+    #[test]
+    fn apply_committed_collects_responses_by_index() {
+        //Arrange
+        let mut log = Log::new();
+        for amount in [5, 10, 20, 40] {
+            log.append(LogEntry {
+                term: 1,
+                command: Some(Command::Custom(Payload(amount))),
+            });
+        }
+        let mut counter = Counter::default();
+
+        //Act
+        let first = log.apply_committed(&mut counter, 2);
+        let second = log.apply_committed(&mut counter, 3);
+
+        //Assert
+        assert_eq!(vec![(1, 5), (2, 15)], first.responses);
+        assert_eq!(vec![(3, 35)], second.responses);
+    }
+
+    impl Snapshottable for Counter {
+        fn snapshot(&self) -> Vec<u8> {
+            self.total.to_be_bytes().to_vec()
+        }
+    }
+
+    fn payload_log(amounts: &[usize]) -> Log<Payload> {
+        let mut log = Log::new();
+        for &amount in amounts {
+            log.append(LogEntry {
+                term: 1,
+                command: Some(Command::Custom(Payload(amount))),
+            });
+        }
+        log
+    }
+
+    #[test]
+    fn verify_determinism_accepts_deterministic_state_machine() {
+        //Arrange
+        let log = payload_log(&[5, 10, 20]);
+
+        //Act
+        verify_determinism(&log, 3, Counter::default);
+    }
+
+    #[test]
+    #[should_panic(expected = "state machine diverged")]
+    fn verify_determinism_rejects_non_deterministic_state_machine() {
+        //Arrange
+        #[derive(Default)]
+        struct Noisy(Vec<u64>);
+        impl StateMachine<Payload> for Noisy {
+            type Response = ();
+
+            fn apply(&mut self, _index: usize, _entry: &LogEntry<Payload>) {
+                self.0.push(rand::random());
+            }
+        }
+        impl Snapshottable for Noisy {
+            fn snapshot(&self) -> Vec<u8> {
+                self.0.iter().flat_map(|value| value.to_be_bytes()).collect()
+            }
+        }
+        let log = payload_log(&[5, 10, 20]);
+
+        //Act
+        verify_determinism(&log, 3, Noisy::default);
+    }
+
+    #[test]
+    fn maintain_snapshots_once_past_entry_threshold() {
+        //Arrange
+        let mut log = Log::new();
+        log.append(LogEntry {
+            term: 1,
+            command: Some(Command::SingleConfiguration{
+                old_configuration: servers!(1, 2),
+                configuration: servers!(1, 2, 3),
+                learners: servers!(),
+                change_id: None,
+            }),
+        });
+        for amount in [5, 10, 20] {
+            log.append(LogEntry {
+                term: 2,
+                command: Some(Command::Custom(Payload(amount))),
+            });
+        }
+        let mut counter = Counter::default();
+        let policy = SnapshotPolicy {
+            max_entries: Some(2),
+            ..SnapshotPolicy::default()
+        };
+
+        //Act
+        let below = maintain(&mut log, &mut counter, 2, &policy);
+        let past = maintain(&mut log, &mut counter, 3, &policy);
+        log.append(LogEntry {
+            term: 2,
+            command: Some(Command::Custom(Payload(80))),
+        });
+        let after = maintain(&mut log, &mut counter, 4, &policy);
+
+        //Assert
+        assert_eq!(None, below);
+        assert_eq!(
+            Some((
+                SnapshotMeta {
+                    last_included_index: 3,
+                    last_included_term: 2,
+                    configuration: servers!(1, 2, 3),
+                },
+                15usize.to_be_bytes().to_vec(),
+            )),
+            past
+        );
+        assert_eq!(None, after);
+        assert_eq!(3, log.base_index());
+        assert_eq!(4, log.last_applied());
+        assert_eq!(35, counter.total);
+    }
+}
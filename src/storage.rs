@@ -0,0 +1,113 @@
+use crate::{
+    log::Log,
+    log_entry::LogEntry,
+};
+use std::{
+    io,
+    ops::Range,
+};
+
+// Where log entries are persisted. Indices are log indices, starting at 1,
+// so a backend holds the same positions as the in-memory `Log`.
+pub trait LogStorage<T> {
+    fn append(&mut self, entries: &[LogEntry<T>]) -> io::Result<()>;
+    // Fails unless every index in `range` is held.
+    fn read(&self, range: Range<usize>) -> io::Result<Vec<LogEntry<T>>>;
+    // Drops the entries at `index` and above.
+    fn truncate(&mut self, index: usize) -> io::Result<()>;
+    fn last_index(&self) -> usize;
+}
+
+impl<T: Clone> LogStorage<T> for Log<T> {
+    fn append(&mut self, entries: &[LogEntry<T>]) -> io::Result<()> {
+        for entry in entries {
+            Log::append(self, entry.clone());
+        }
+        Ok(())
+    }
+
+    fn read(&self, range: Range<usize>) -> io::Result<Vec<LogEntry<T>>> {
+        range
+            .map(|index| {
+                self.get(index).cloned().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("entry {} is not in the log", index))
+                })
+            })
+            .collect()
+    }
+
+    // Entries already applied, or compacted into the snapshot, are committed
+    // and cannot be truncated.
+    fn truncate(&mut self, index: usize) -> io::Result<()> {
+        if index <= self.base_index().max(self.last_applied()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("entry {} is already applied", index),
+            ));
+        }
+        if index <= Log::last_index(self) {
+            self.truncate_from(index);
+        }
+        Ok(())
+    }
+
+    fn last_index(&self) -> usize {
+        Log::last_index(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        log_entry::Command,
+        testing::Payload,
+    };
+
+    fn entry(term: u64, value: usize) -> LogEntry<Payload> {
+        LogEntry {
+            term,
+            command: Some(Command::Custom(Payload(value))),
+        }
+    }
+
+    // Exercises a backend only through the trait, as the core algorithm does.
+    fn replace_suffix<S: LogStorage<Payload>>(storage: &mut S, index: usize, entries: &[LogEntry<Payload>]) -> io::Result<Vec<LogEntry<Payload>>> {
+        storage.truncate(index)?;
+        storage.append(entries)?;
+        storage.read(1..storage.last_index() + 1)
+    }
+
+    #[test]
+    fn log_is_a_storage_backend() {
+        //Arrange
+        let mut log = Log::new();
+        LogStorage::append(&mut log, &[entry(1, 1), entry(1, 2), entry(2, 3)]).unwrap();
+
+        //Act
+        let entries = replace_suffix(&mut log, 3, &[entry(3, 4), entry(3, 5)]).unwrap();
+
+        //Assert
+        assert_eq!(vec![entry(1, 1), entry(1, 2), entry(3, 4), entry(3, 5)], entries);
+        assert_eq!(4, LogStorage::last_index(&log));
+    }
+
+    #[test]
+    fn reading_missing_or_truncating_applied_entries_fails() {
+        //Arrange
+        let mut log = Log::new();
+        LogStorage::append(&mut log, &[entry(1, 1), entry(1, 2), entry(2, 3)]).unwrap();
+        log.compact(1);
+
+        //Act
+        let compacted = log.read(1..3);
+        let beyond = log.read(3..5);
+        let applied = log.truncate(1);
+
+        //Assert
+        assert_eq!(io::ErrorKind::NotFound, compacted.unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, beyond.unwrap_err().kind());
+        assert_eq!(io::ErrorKind::InvalidInput, applied.unwrap_err().kind());
+        assert_eq!(Ok(vec![entry(1, 2)]), log.read(2..3).map_err(|error| error.kind()));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::log_entry::{CommandDecodeError, CustomCommand};
+use serde_json::{
+    json,
+    Value as JsonValue
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde-derive", derive(serde::Serialize, serde::Deserialize))]
+pub struct Payload(pub usize);
+
+impl CustomCommand for Payload {
+    fn command_type(&self) -> &'static str {
+        "Payload"
+    }
+
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "value": self.0,
+        })
+    }
+
+    fn from_json(json: &JsonValue) -> Result<Self, CommandDecodeError> {
+        Ok(Payload(
+            json.get("command")
+                .and_then(|command| command.get("value"))
+                .and_then(JsonValue::as_u64)
+                .unwrap_or(0) as usize
+        ))
+    }
+}
+
+// Like `maplit::hashset!`, but for configurations: `servers!(1, 2)` is the
+// set of `ServerId(1)` and `ServerId(2)`.
+macro_rules! servers {
+    ($($id:expr),* $(,)?) => {
+        maplit::hashset!($($crate::server_id::ServerId($id)),*)
+    };
+}
+
+pub(crate) use servers;